@@ -1,24 +1,437 @@
-use std::time::{Instant, Duration};
+//! Measurements shared by the host programs of every zkVM.
+//!
+//! Each host times its phases with [`Benchmarker`] and prints them with [`Measurements::report`]
+//! and [`Benchmarker::report_phases`], so the proving time and peak memory of RISC Zero, SP1,
+//! Valida and the native baseline are taken and logged by the same code and can be compared
+//! directly.
 
+use std::cell::RefCell;
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub mod energy;
+pub mod memory;
+pub mod phase;
+pub mod record;
+pub mod results;
+mod rusage;
+pub mod stats;
+
+use energy::{Energy, EnergyMeter, DEFAULT_SYSFS_ROOT, DEFAULT_TDP_WATTS};
+use phase::{OpenPhase, Phase, PhaseGuard};
+use stats::Summary;
+
+/// How often the memory of child processes is sampled unless `BENCH_MEMORY_SAMPLE_MS` says
+/// otherwise.
+const DEFAULT_SAMPLE_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Records the phases of a host program, see [`phase`].
 pub struct Benchmarker {
-    start_time: Option<Instant>,
+    config: RunConfig,
+    energy: EnergyMeter,
+    open: RefCell<Vec<OpenPhase>>,
+    /// The phases that are not nested in another, once closed
+    closed: RefCell<Vec<Phase>>,
 }
 
 impl Benchmarker {
     pub fn new() -> Benchmarker {
-        Benchmarker { 
-            start_time: None,
+        Benchmarker::with_config(RunConfig::default())
+    }
+
+    /// Repeat phases, sample memory and measure energy as `config` says.
+    pub fn with_config(config: RunConfig) -> Benchmarker {
+        Benchmarker {
+            energy: EnergyMeter::new(&config.sysfs_root, config.tdp_watts),
+            config,
+            open: RefCell::new(Vec::new()),
+            closed: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Run `f` as the phase `name`.
+    pub fn phase<T>(&self, name: &str, f: impl FnOnce() -> T) -> T {
+        let _guard = self.span(name);
+        f()
+    }
+
+    /// Open the phase `name`, which lasts until the guard is dropped or finished. See [`memory`]
+    /// and [`energy`] for how its peak memory and energy are measured.
+    pub fn span(&self, name: &str) -> PhaseGuard<'_> {
+        let mut open = self.open.borrow_mut();
+        // the new phase resets the peak memory the open ones are measuring
+        for phase in open.iter_mut() {
+            phase.memory.carry_peak();
+        }
+        open.push(OpenPhase::start(
+            name,
+            self.config.sample_interval,
+            &self.energy,
+        ));
+        PhaseGuard {
+            benchmarker: self,
+            depth: Some(open.len()),
+        }
+    }
+
+    /// Run `f` `warmup` times unmeasured, then `runs` times measured as the phase `name`.
+    ///
+    /// Returns the result of the last run, so the proof of the final run can be used for the
+    /// next phase.
+    pub fn repeat<T>(&self, name: &str, mut f: impl FnMut() -> T) -> (T, Measurements) {
+        for _ in 0..self.config.warmup {
+            f();
+        }
+
+        let mut measurements = Measurements::default();
+        let mut result = None;
+        for _ in 0..self.config.runs.max(1) {
+            let guard = self.span(name);
+            result = Some(f());
+            if let Some(phase) = guard.finish() {
+                measurements.samples.push(phase.measurement());
+            }
+        }
+        (result.unwrap(), measurements)
+    }
+
+    /// The phases closed so far that are not nested in another.
+    pub fn phases(&self) -> Vec<Phase> {
+        self.closed.borrow().clone()
+    }
+
+    /// Print the tree of closed phases as one `Phases: [...]` line of JSON.
+    pub fn report_phases(&self) {
+        let phases = serde_json::to_string(&*self.closed.borrow()).unwrap();
+        println!("Phases: {}", phases);
+    }
+
+    /// Close the phase opened at `depth` and the phases open inside it.
+    fn close(&self, depth: usize) -> Option<Phase> {
+        let mut open = self.open.borrow_mut();
+        let mut closed = None;
+        while open.len() >= depth {
+            let phase = open.pop().unwrap().close(&self.energy);
+            match open.last_mut() {
+                Some(parent) => parent.phases.push(phase.clone()),
+                None => self.closed.borrow_mut().push(phase.clone()),
+            }
+            closed = Some(phase);
         }
+        closed
     }
+}
+
+impl Default for Benchmarker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How often each benchmarked phase is repeated, read from `BENCH_WARMUP` and `BENCH_RUNS`, how
+/// often the memory of child processes is sampled, read from `BENCH_MEMORY_SAMPLE_MS`, and where
+/// the energy comes from, read from `BENCH_SYSFS_ROOT` and `BENCH_TDP_WATTS`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunConfig {
+    pub warmup: usize,
+    pub runs: usize,
+    pub sample_interval: Duration,
+    /// Where the RAPL counters are looked for, under `class/powercap`
+    pub sysfs_root: PathBuf,
+    /// The TDP of the CPU in watts, for the energy estimate where RAPL is unavailable
+    pub tdp_watts: u32,
+}
+
+impl RunConfig {
+    /// Defaults to a single measured run without warmup, sampling every 10ms, reading RAPL from
+    /// `/sys` and estimating with a TDP of 65W.
+    pub fn from_env() -> RunConfig {
+        let read = |name: &str, default: usize| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .unwrap_or(default)
+        };
+        RunConfig {
+            warmup: read("BENCH_WARMUP", 0),
+            runs: read("BENCH_RUNS", 1).max(1),
+            sample_interval: Duration::from_millis(
+                read(
+                    "BENCH_MEMORY_SAMPLE_MS",
+                    DEFAULT_SAMPLE_INTERVAL.as_millis() as usize,
+                )
+                .max(1) as u64,
+            ),
+            sysfs_root: std::env::var_os("BENCH_SYSFS_ROOT")
+                .map_or_else(|| PathBuf::from(DEFAULT_SYSFS_ROOT), PathBuf::from),
+            tdp_watts: read("BENCH_TDP_WATTS", DEFAULT_TDP_WATTS as usize) as u32,
+        }
+    }
+
+    /// Log the configuration so the results record how many runs were made.
+    pub fn print(&self) {
+        println!("Warmup runs: {}", self.warmup);
+        println!("Measured runs: {}", self.runs);
+    }
+}
 
-    pub fn start_benchmark(&mut self) {
-        self.start_time = Some(Instant::now());
+impl Default for RunConfig {
+    fn default() -> Self {
+        RunConfig {
+            warmup: 0,
+            runs: 1,
+            sample_interval: DEFAULT_SAMPLE_INTERVAL,
+            sysfs_root: PathBuf::from(DEFAULT_SYSFS_ROOT),
+            tdp_watts: DEFAULT_TDP_WATTS,
+        }
+    }
+}
+
+/// Whether the host proves the program or only executes it, read from `BENCH_MODE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    #[default]
+    Prove,
+    /// Run the SDK executor and report cycle counts without generating a proof
+    Execute,
+}
+
+impl Mode {
+    /// Defaults to proving unless `BENCH_MODE=execute`.
+    pub fn from_env() -> Mode {
+        match std::env::var("BENCH_MODE").as_deref() {
+            Ok("execute") => Mode::Execute,
+            _ => Mode::Prove,
+        }
     }
 
-    pub fn end_benchmark(&mut self) -> Option<Duration> {
-        match self.start_time {
-            Some(start) => Some(start.elapsed()),
-            None => None,
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Prove => "prove",
+            Mode::Execute => "execute",
         }
     }
-}
\ No newline at end of file
+}
+
+/// The proof the host generates, read from `BENCH_PROOF_KIND`.
+///
+/// Each zkVM maps the kinds it supports to its own prover options: RISC Zero supports composite,
+/// succinct and groth16, SP1 supports composite (its core proof), compressed, groth16 and plonk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ProofKind {
+    /// One proof per segment or shard, the default
+    #[default]
+    Composite,
+    Succinct,
+    Compressed,
+    Groth16,
+    Plonk,
+}
+
+impl ProofKind {
+    pub const ALL: [ProofKind; 5] = [
+        ProofKind::Composite,
+        ProofKind::Succinct,
+        ProofKind::Compressed,
+        ProofKind::Groth16,
+        ProofKind::Plonk,
+    ];
+
+    /// Defaults to a composite proof when `BENCH_PROOF_KIND` is not set.
+    pub fn from_env() -> Result<ProofKind, String> {
+        match std::env::var("BENCH_PROOF_KIND") {
+            Ok(name) => name.parse(),
+            Err(_) => Ok(ProofKind::default()),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProofKind::Composite => "composite",
+            ProofKind::Succinct => "succinct",
+            ProofKind::Compressed => "compressed",
+            ProofKind::Groth16 => "groth16",
+            ProofKind::Plonk => "plonk",
+        }
+    }
+
+    /// Log the proof kind so the results record which proof was measured.
+    pub fn print(&self) {
+        println!("Proof kind: {}", self.name());
+    }
+
+    /// Report that `zkvm` cannot generate this kind of proof and exit with an error.
+    pub fn unsupported(&self, zkvm: &str) -> ! {
+        eprintln!(
+            "Unsupported proof kind: {} proofs are not available for {}",
+            self.name(),
+            zkvm
+        );
+        std::process::exit(2);
+    }
+}
+
+impl std::str::FromStr for ProofKind {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        ProofKind::ALL
+            .into_iter()
+            .find(|kind| kind.name() == name)
+            .ok_or_else(|| {
+                format!(
+                    "unknown proof kind {:?}, expected composite, succinct, compressed, groth16 or plonk",
+                    name
+                )
+            })
+    }
+}
+
+/// One measured run of a phase.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Measurement {
+    pub duration: Duration,
+    /// CPU time of the process and of the child processes that exited during the run
+    pub user_time: Duration,
+    pub system_time: Duration,
+    /// The most threads running at once
+    pub threads: u64,
+    /// Peak resident memory of the benchmarked process in KB
+    pub peak_memory: u64,
+    /// Peak resident memory of the processes it started in KB, 0 if it started none
+    pub children_peak_memory: u64,
+    pub energy: Energy,
+}
+
+impl Measurement {
+    pub fn cpu_time(&self) -> Duration {
+        self.user_time + self.system_time
+    }
+
+    /// The number of cores kept busy on average, 0 for a run too short to time.
+    pub fn parallelism(&self) -> f64 {
+        let wall_time = self.duration.as_secs_f64();
+        if wall_time > 0.0 {
+            self.cpu_time().as_secs_f64() / wall_time
+        } else {
+            0.0
+        }
+    }
+}
+
+/// The measured runs of one phase.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Measurements {
+    pub samples: Vec<Measurement>,
+}
+
+impl Measurements {
+    pub fn time_summary(&self) -> Option<Summary> {
+        let durations: Vec<f64> = self
+            .samples
+            .iter()
+            .map(|sample| sample.duration.as_secs_f64())
+            .collect();
+        Summary::from_samples(&durations)
+    }
+
+    pub fn cpu_time_summary(&self) -> Option<Summary> {
+        let cpu_times: Vec<f64> = self
+            .samples
+            .iter()
+            .map(|sample| sample.cpu_time().as_secs_f64())
+            .collect();
+        Summary::from_samples(&cpu_times)
+    }
+
+    pub fn parallelism_summary(&self) -> Option<Summary> {
+        let parallelism: Vec<f64> = self.samples.iter().map(Measurement::parallelism).collect();
+        Summary::from_samples(&parallelism)
+    }
+
+    pub fn energy_summary(&self) -> Option<Summary> {
+        let energy: Vec<f64> = self
+            .samples
+            .iter()
+            .map(|sample| sample.energy.joules)
+            .collect();
+        Summary::from_samples(&energy)
+    }
+
+    pub fn memory_summary(&self) -> Option<Summary> {
+        let memory: Vec<f64> = self
+            .samples
+            .iter()
+            .map(|sample| sample.peak_memory as f64)
+            .collect();
+        Summary::from_samples(&memory)
+    }
+
+    /// `None` if no run started a child process.
+    pub fn children_memory_summary(&self) -> Option<Summary> {
+        if self
+            .samples
+            .iter()
+            .all(|sample| sample.children_peak_memory == 0)
+        {
+            return None;
+        }
+        let memory: Vec<f64> = self
+            .samples
+            .iter()
+            .map(|sample| sample.children_peak_memory as f64)
+            .collect();
+        Summary::from_samples(&memory)
+    }
+
+    /// Print every sample as `<phase> time: ...`, `<phase> user time: ...`,
+    /// `<phase> system time: ...`, `<phase> threads: ...`, `<phase> parallelism: ...`,
+    /// `<phase> energy: ... J` and `<phase> peak memory: ... KB`, followed by summary lines. An
+    /// estimated energy is printed as `... J (estimate)`. `<phase> children peak memory: ... KB`
+    /// lines are added for the phases that start child processes.
+    pub fn report(&self, phase: &str) {
+        let children_summary = self.children_memory_summary();
+        for sample in &self.samples {
+            println!("{} time: {:?}", phase, sample.duration);
+            println!("{} user time: {:?}", phase, sample.user_time);
+            println!("{} system time: {:?}", phase, sample.system_time);
+            println!("{} threads: {}", phase, sample.threads);
+            println!("{} parallelism: {:.3}", phase, sample.parallelism());
+            println!(
+                "{} energy: {:.6} J{}",
+                phase,
+                sample.energy.joules,
+                if sample.energy.estimated {
+                    " (estimate)"
+                } else {
+                    ""
+                }
+            );
+            println!("{} peak memory: {} KB", phase, sample.peak_memory);
+            if children_summary.is_some() {
+                println!(
+                    "{} children peak memory: {} KB",
+                    phase, sample.children_peak_memory
+                );
+            }
+        }
+        if let Some(summary) = self.time_summary() {
+            println!("{} time summary (s): {}", phase, summary);
+        }
+        if let Some(summary) = self.cpu_time_summary() {
+            println!("{} CPU time summary (s): {}", phase, summary);
+        }
+        if let Some(summary) = self.parallelism_summary() {
+            println!("{} parallelism summary: {}", phase, summary);
+        }
+        if let Some(summary) = self.energy_summary() {
+            println!("{} energy summary (J): {}", phase, summary);
+        }
+        if let Some(summary) = self.memory_summary() {
+            println!("{} peak memory summary (KB): {}", phase, summary);
+        }
+        if let Some(summary) = children_summary {
+            println!("{} children peak memory summary (KB): {}", phase, summary);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! Energy used by a measured phase.
+//!
+//! On Linux the RAPL counters of the CPU are exposed through powercap as
+//! `<sysfs>/class/powercap/intel-rapl:<n>/energy_uj`, one per package, in microjoules. They are
+//! read when a phase starts and when it ends. A counter wraps around to 0 after
+//! `max_energy_range_uj`, which is corrected for once; a phase long enough for a counter to wrap
+//! twice is under-reported, and a counter read above its range makes the phase an estimate. The
+//! subzones of a package (its cores, uncore or DRAM) are already part of its count and the `psys`
+//! zone covers the packages, so neither is added again.
+//!
+//! Without RAPL, or where its counters cannot be read (recent kernels only let root read them),
+//! the energy is estimated from the CPU time of the phase: every core kept busy is assumed to draw
+//! an equal share of the TDP of the CPU. Such energies are marked as estimates wherever they are
+//! reported.
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Where sysfs is mounted unless `BENCH_SYSFS_ROOT` says otherwise.
+pub const DEFAULT_SYSFS_ROOT: &str = "/sys";
+
+/// The TDP the estimate assumes unless `BENCH_TDP_WATTS` says otherwise, that of a typical
+/// desktop CPU.
+pub const DEFAULT_TDP_WATTS: u32 = 65;
+
+/// The energy of a phase.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Energy {
+    pub joules: f64,
+    /// Whether it was estimated from the CPU time instead of measured with RAPL
+    pub estimated: bool,
+}
+
+/// Reads the RAPL counters, or estimates the energy where there are none.
+#[derive(Debug, Clone)]
+pub struct EnergyMeter {
+    zones: Vec<Zone>,
+    /// Watts drawn by one busy core, for the estimate
+    core_watts: f64,
+}
+
+#[derive(Debug, Clone)]
+struct Zone {
+    energy: PathBuf,
+    max_energy_range: u64,
+}
+
+/// The counters of every zone when a phase started, `None` if they could not be read.
+#[derive(Debug, Clone)]
+pub struct EnergyReading(Option<Vec<u64>>);
+
+impl EnergyMeter {
+    /// Find the RAPL packages under `sysfs_root`. The energy is estimated with `tdp_watts` if
+    /// there are none, or if their counters cannot be read.
+    pub fn new(sysfs_root: &Path, tdp_watts: u32) -> EnergyMeter {
+        let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+        let mut zones = find_zones(sysfs_root);
+        if zones.iter().any(|zone| zone.read().is_none()) {
+            zones.clear();
+        }
+        EnergyMeter {
+            zones,
+            core_watts: tdp_watts as f64 / cores as f64,
+        }
+    }
+
+    /// Whether the energy is estimated because RAPL is unavailable.
+    pub fn is_estimate(&self) -> bool {
+        self.zones.is_empty()
+    }
+
+    pub fn read(&self) -> EnergyReading {
+        if self.is_estimate() {
+            return EnergyReading(None);
+        }
+        EnergyReading(self.zones.iter().map(Zone::read).collect())
+    }
+
+    /// The energy since `start`, estimated from `cpu_time` if a counter could not be read or its
+    /// wraparound cannot be corrected for.
+    pub fn since(&self, start: &EnergyReading, cpu_time: Duration) -> Energy {
+        let measured = start.0.as_ref().and_then(|start| {
+            self.zones
+                .iter()
+                .zip(start)
+                .map(|(zone, &start)| {
+                    let end = zone.read()?;
+                    if end >= start {
+                        Some(end - start)
+                    } else {
+                        // the counter runs from 0 to `max_energy_range` inclusive, a start above
+                        // it cannot be corrected for
+                        let until_wrap = zone.max_energy_range.checked_sub(start)?;
+                        until_wrap.checked_add(end)?.checked_add(1)
+                    }
+                })
+                .sum::<Option<u64>>()
+        });
+        match measured {
+            Some(microjoules) => Energy {
+                joules: microjoules as f64 / 1e6,
+                estimated: false,
+            },
+            None => Energy {
+                joules: cpu_time.as_secs_f64() * self.core_watts,
+                estimated: true,
+            },
+        }
+    }
+}
+
+impl Zone {
+    fn read(&self) -> Option<u64> {
+        read_number(&self.energy)
+    }
+}
+
+/// The `intel-rapl:<n>` packages, leaving out their `intel-rapl:<n>:<m>` subzones and `psys`.
+fn find_zones(sysfs_root: &Path) -> Vec<Zone> {
+    let Ok(entries) = fs::read_dir(sysfs_root.join("class/powercap")) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let package = name.strip_prefix("intel-rapl:")?;
+            if package.contains(':') {
+                return None;
+            }
+            let dir = entry.path();
+            let zone_name = fs::read_to_string(dir.join("name")).unwrap_or_default();
+            if zone_name.trim() == "psys" {
+                return None;
+            }
+            Some(Zone {
+                max_energy_range: read_number(&dir.join("max_energy_range_uj"))?,
+                energy: dir.join("energy_uj"),
+            })
+        })
+        .collect()
+}
+
+fn read_number(path: &Path) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
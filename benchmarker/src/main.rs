@@ -1,3 +1,163 @@
+use benchmarker::record::{locked_version, parse_input_parameters, RunRecord};
+use benchmarker::results::{
+    BenchmarkResult, ExecutionResult, NativeResult, OutputCheck, ParseOptions,
+};
+use std::path::Path;
+use std::{fs, process};
+
+const USAGE: &str = "\
+Usage: benchmarker -z ZKVM -t TEST_NAME -r RUST_BENCH_LOG [-n NATIVE_RESULTS]
+                   [-l CARGO_LOCK] [--csv CSV_FILE] [--no-cycles] -o OUTPUT_FILE
+       benchmarker -z native -t TEST_NAME -r RUST_BENCH_LOG [--csv CSV_FILE] -o OUTPUT_FILE
+       benchmarker -z ZKVM -t TEST_NAME -r RUST_BENCH_LOG --execute [--csv CSV_FILE] -o OUTPUT_FILE
+Options:
+  -z          : zkVM that produced the logs (risc0, sp1, valida or native)
+  -t          : Name of the test template
+  -r          : Path to rust benchmark log file
+  -n          : Path to native baseline results file (optional, skipped if missing)
+  -l          : Path to the host Cargo.lock, used to record the SDK version (optional)
+  -o          : Path to the JSON output file
+  --csv       : Path to a CSV file the metrics are appended to (optional)
+  --no-cycles : Do not require a cycle count (zkVMs that do not report one)
+  --execute   : Parse the log of an execute-only run (BENCH_MODE=execute)
+  -h          : Display this help message";
+
+struct Args {
+    zkvm: String,
+    test_name: String,
+    rust_bench_log: String,
+    native_results: Option<String>,
+    cargo_lock: Option<String>,
+    output_file: String,
+    csv_file: Option<String>,
+    require_cycles: bool,
+    execute: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut zkvm = None;
+    let mut test_name = None;
+    let mut rust_bench_log = None;
+    let mut native_results = None;
+    let mut cargo_lock = None;
+    let mut output_file = None;
+    let mut csv_file = None;
+    let mut require_cycles = true;
+    let mut execute = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-z" => zkvm = args.next(),
+            "-t" => test_name = args.next(),
+            "-r" => rust_bench_log = args.next(),
+            "-n" => native_results = args.next(),
+            "-l" => cargo_lock = args.next(),
+            "-o" => output_file = args.next(),
+            "--csv" => csv_file = args.next(),
+            "--no-cycles" => require_cycles = false,
+            "--execute" => execute = true,
+            "-h" | "--help" => return Err(String::new()),
+            other => return Err(format!("Error: Unknown argument {}", other)),
+        }
+    }
+
+    let (Some(zkvm), Some(test_name), Some(rust_bench_log), Some(output_file)) =
+        (zkvm, test_name, rust_bench_log, output_file)
+    else {
+        return Err("Error: Missing required arguments".to_string());
+    };
+
+    Ok(Args {
+        zkvm,
+        test_name,
+        rust_bench_log,
+        native_results,
+        cargo_lock,
+        output_file,
+        csv_file,
+        require_cycles,
+        execute,
+    })
+}
+
+/// The crate whose locked version is reported as the SDK version of each zkVM.
+fn sdk_package(zkvm: &str) -> Option<&'static str> {
+    match zkvm {
+        "risc0" => Some("risc0-zkvm"),
+        "sp1" => Some("sp1-sdk"),
+        "valida" => Some("valida-rs"),
+        _ => None,
+    }
+}
+
+fn fail(message: String) -> ! {
+    eprintln!("Error: {}", message);
+    process::exit(1);
+}
+
+fn read_file(path: &str) -> String {
+    fs::read_to_string(path).unwrap_or_else(|err| fail(format!("Failed to read {}: {}", path, err)))
+}
+
 fn main() {
-    println!("Hello, world!");
+    let args = parse_args().unwrap_or_else(|message| {
+        if !message.is_empty() {
+            eprintln!("{}", message);
+        }
+        eprintln!("{}", USAGE);
+        process::exit(1);
+    });
+
+    let rust_bench_log = read_file(&args.rust_bench_log);
+    // a missing lockfile only leaves the SDK version empty
+    let sdk_version = args.cargo_lock.as_deref().and_then(|path| {
+        let package = sdk_package(&args.zkvm)?;
+        locked_version(&fs::read_to_string(path).ok()?, package)
+    });
+    let record = RunRecord::new(&args.zkvm, &args.test_name, sdk_version);
+
+    let mut record = if args.zkvm == "native" {
+        let result = NativeResult::from_log(&rust_bench_log)
+            .unwrap_or_else(|err| fail(format!("{} ({})", err, args.rust_bench_log)));
+        record.with_native(&result)
+    } else if args.execute {
+        let result = ExecutionResult::from_log(&rust_bench_log)
+            .unwrap_or_else(|err| fail(format!("{} ({})", err, args.rust_bench_log)));
+        record.with_execution(&result)
+    } else {
+        let native_execution_time = args
+            .native_results
+            .filter(|path| Path::new(path).exists())
+            .and_then(|path| {
+                RunRecord::read_json(Path::new(&path))
+                    .unwrap_or_else(|err| {
+                        fail(format!("Failed to parse native results {}: {}", path, err))
+                    })
+                    .metric("execution_time")
+            });
+        let options = ParseOptions {
+            require_cycles: args.require_cycles,
+        };
+        let result = BenchmarkResult::from_log(&rust_bench_log, native_execution_time, options)
+            .unwrap_or_else(|err| fail(format!("{} ({})", err, args.rust_bench_log)));
+        record.with_benchmark(&result)
+    };
+    record.inputs = parse_input_parameters(&rust_bench_log);
+
+    record
+        .write_json(Path::new(&args.output_file))
+        .unwrap_or_else(|err| fail(format!("Failed to write {}: {}", args.output_file, err)));
+    if let Some(csv_file) = &args.csv_file {
+        record
+            .append_csv(Path::new(csv_file))
+            .unwrap_or_else(|err| fail(format!("Failed to write {}: {}", csv_file, err)));
+    }
+    // the results are kept, but a wrong answer must not look like a successful run
+    if record.output_check == Some(OutputCheck::Failed) {
+        fail(format!(
+            "Committed outputs do not match the expected outputs ({})",
+            args.rust_bench_log
+        ));
+    }
 }
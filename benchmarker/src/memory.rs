@@ -0,0 +1,219 @@
+//! Peak memory and thread count of a measured phase.
+//!
+//! On Linux the kernel keeps the peak resident set of the process as `VmHWM` in
+//! `/proc/self/status`. Writing `5` to `/proc/self/clear_refs` resets it to the current resident
+//! set, so it is reset when a phase starts and read when it ends, which catches peaks that fall
+//! between two samples. Programs a phase runs as separate processes, like the Valida prover, are
+//! measured on their own: the resident sets of all descendants are read from
+//! `/proc/<pid>/status` at the sampling interval, and `getrusage(RUSAGE_CHILDREN)` adds the exact
+//! peak of a child that exited during the phase when it is above those of earlier phases. A phase
+//! nested in another resets the peak too, so the peak of the outer phase so far is kept first.
+//!
+//! Where the peak cannot be reset the resident set of the process is sampled as well, and without
+//! `/proc` the peak since the process started is taken from `getrusage(RUSAGE_SELF)`.
+//!
+//! The threads of the process and its descendants are counted at every sample, leaving out the
+//! sampling threads themselves.
+
+use std::fs;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::rusage::{resource_usage, Who};
+
+/// The sampling threads running in this process.
+static SAMPLERS: AtomicUsize = AtomicUsize::new(0);
+
+/// The peaks of a phase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Peaks {
+    /// Resident memory of the benchmarked process itself in KB
+    pub process: u64,
+    /// Resident memory of its child processes together in KB, 0 if it started none
+    pub children: u64,
+    /// The most threads of the process and its child processes running at once, 0 without
+    /// `/proc`
+    pub threads: u64,
+}
+
+pub(crate) struct MemoryMeter {
+    /// Whether `VmHWM` was reset when the phase started, so it is the peak of the phase
+    peak_reset: bool,
+    /// The peak before a nested phase reset `VmHWM`
+    carried: u64,
+    children_before: Option<u64>,
+    samples: Samples,
+    is_running: Arc<AtomicBool>,
+    sampler: Option<thread::JoinHandle<()>>,
+}
+
+impl MemoryMeter {
+    pub fn start(interval: Duration) -> MemoryMeter {
+        let peak_reset = fs::write("/proc/self/clear_refs", "5").is_ok();
+        let samples = Samples {
+            process_too: !peak_reset,
+            ..Samples::default()
+        };
+        let is_running = Arc::new(AtomicBool::new(true));
+
+        SAMPLERS.fetch_add(1, Ordering::SeqCst);
+        let sampler = {
+            let samples = samples.clone();
+            let is_running = is_running.clone();
+            thread::spawn(move || {
+                while is_running.load(Ordering::SeqCst) {
+                    thread::sleep(interval);
+                    samples.sample();
+                }
+            })
+        };
+        // sample once up front so phases shorter than the interval are not reported as 0
+        samples.sample();
+        MemoryMeter {
+            peak_reset,
+            carried: 0,
+            children_before: children_max_rss(),
+            samples,
+            is_running,
+            sampler: Some(sampler),
+        }
+    }
+
+    /// Keep the peak so far before a nested phase resets it.
+    pub fn carry_peak(&mut self) {
+        if self.peak_reset {
+            if let Some(peak) = status_kilobytes("self", "VmHWM:") {
+                self.carried = self.carried.max(peak);
+            }
+        }
+    }
+
+    pub fn stop(mut self) -> Peaks {
+        self.samples.sample();
+        self.stop_sampling();
+
+        let high_water_mark = self
+            .peak_reset
+            .then(|| status_kilobytes("self", "VmHWM:"))
+            .flatten();
+        let process = match (high_water_mark, self.samples.process.load(Ordering::SeqCst)) {
+            (Some(peak), _) => peak.max(self.carried),
+            (None, 0) => resource_usage(Who::Process).map_or(0, |usage| usage.max_rss),
+            (None, sampled) => sampled,
+        };
+        let exited = match (self.children_before, children_max_rss()) {
+            (Some(before), Some(after)) if after > before => after,
+            _ => 0,
+        };
+        Peaks {
+            process,
+            children: self.samples.children.load(Ordering::SeqCst).max(exited),
+            threads: self.samples.threads.load(Ordering::SeqCst),
+        }
+    }
+
+    fn stop_sampling(&mut self) {
+        self.is_running.store(false, Ordering::SeqCst);
+        if let Some(handle) = self.sampler.take() {
+            let _ = handle.join();
+            SAMPLERS.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+impl Drop for MemoryMeter {
+    fn drop(&mut self) {
+        self.stop_sampling();
+    }
+}
+
+/// The highest values seen so far, shared with the sampling thread.
+#[derive(Clone, Default)]
+struct Samples {
+    /// Whether the resident memory of the process itself is sampled too
+    process_too: bool,
+    process: Arc<AtomicU64>,
+    children: Arc<AtomicU64>,
+    threads: Arc<AtomicU64>,
+}
+
+impl Samples {
+    fn sample(&self) {
+        let Some(status) = read_status("self") else {
+            return;
+        };
+        if self.process_too {
+            if let Some(resident) = field_kilobytes(&status, "VmRSS:") {
+                self.process.fetch_max(resident, Ordering::SeqCst);
+            }
+        }
+        let mut threads = field_number(&status, "Threads:").map_or(0, |threads| {
+            threads.saturating_sub(SAMPLERS.load(Ordering::SeqCst) as u64)
+        });
+        let mut resident = 0;
+        for pid in descendants(std::process::id()) {
+            if let Some(status) = read_status(&pid.to_string()) {
+                resident += field_kilobytes(&status, "VmRSS:").unwrap_or(0);
+                threads += field_number(&status, "Threads:").unwrap_or(0);
+            }
+        }
+        self.children.fetch_max(resident, Ordering::SeqCst);
+        self.threads.fetch_max(threads, Ordering::SeqCst);
+    }
+}
+
+/// The children of `pid`, their children and so on, as listed by every thread of each process.
+fn descendants(pid: u32) -> Vec<u32> {
+    let mut found = Vec::new();
+    let mut pending = vec![pid];
+    while let Some(pid) = pending.pop() {
+        let Ok(tasks) = fs::read_dir(format!("/proc/{}/task", pid)) else {
+            continue;
+        };
+        for task in tasks.flatten() {
+            let Ok(children) = fs::read_to_string(task.path().join("children")) else {
+                continue;
+            };
+            for child in children
+                .split_whitespace()
+                .filter_map(|pid| pid.parse().ok())
+            {
+                found.push(child);
+                pending.push(child);
+            }
+        }
+    }
+    found
+}
+
+fn read_status(pid: &str) -> Option<String> {
+    fs::read_to_string(format!("/proc/{}/status", pid)).ok()
+}
+
+/// Read a `<field> <number> kB` line of `/proc/<pid>/status`.
+fn status_kilobytes(pid: &str, field: &str) -> Option<u64> {
+    field_kilobytes(&read_status(pid)?, field)
+}
+
+fn field_kilobytes(status: &str, field: &str) -> Option<u64> {
+    field_value(status, field)
+        .and_then(|value| value.strip_suffix("kB"))
+        .and_then(|number| number.trim().parse().ok())
+}
+
+fn field_number(status: &str, field: &str) -> Option<u64> {
+    field_value(status, field).and_then(|value| value.parse().ok())
+}
+
+fn field_value<'a>(status: &'a str, field: &str) -> Option<&'a str> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix(field))
+        .map(str::trim)
+}
+
+fn children_max_rss() -> Option<u64> {
+    resource_usage(Who::Children).map(|usage| usage.max_rss)
+}
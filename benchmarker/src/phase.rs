@@ -0,0 +1,152 @@
+//! Named, nested phases of a host program.
+//!
+//! A phase is opened with [`Benchmarker::phase`](crate::Benchmarker::phase) or
+//! [`Benchmarker::span`](crate::Benchmarker::span) and closed when its closure returns or its
+//! guard is dropped. Phases opened while another is open are recorded inside it, so the tree
+//! shows where the time of a step like proving goes, down to key generation or serialization.
+
+use crate::energy::{Energy, EnergyMeter, EnergyReading};
+use crate::memory::MemoryMeter;
+use crate::rusage::{resource_usage, Who};
+use crate::{Benchmarker, Measurement};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// A closed phase and the phases it contains.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Phase {
+    pub name: String,
+    /// Wall-clock time in seconds
+    pub wall_time: f64,
+    /// User and system CPU time in seconds, over all threads of the process and of the child
+    /// processes that exited during the phase
+    pub cpu_time: f64,
+    pub user_time: f64,
+    pub system_time: f64,
+    /// The most threads running at once, see [`memory`](crate::memory)
+    pub threads: u64,
+    /// CPU time over wall time, the number of cores kept busy on average
+    pub parallelism: f64,
+    /// Peak resident memory of the process in KB
+    pub peak_memory: u64,
+    /// Peak resident memory of the processes it started in KB, 0 if it started none
+    pub children_peak_memory: u64,
+    /// Energy of the whole CPU, see [`energy`](crate::energy)
+    pub energy: Energy,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub phases: Vec<Phase>,
+}
+
+impl Phase {
+    pub fn measurement(&self) -> Measurement {
+        Measurement {
+            duration: Duration::from_secs_f64(self.wall_time),
+            user_time: Duration::from_secs_f64(self.user_time),
+            system_time: Duration::from_secs_f64(self.system_time),
+            threads: self.threads,
+            peak_memory: self.peak_memory,
+            children_peak_memory: self.children_peak_memory,
+            energy: self.energy,
+        }
+    }
+}
+
+/// Closes its phase when dropped, or with [`PhaseGuard::finish`] to get the phase back.
+#[must_use = "the phase is closed when the guard is dropped"]
+pub struct PhaseGuard<'a> {
+    pub(crate) benchmarker: &'a Benchmarker,
+    /// The number of open phases once this one was opened, `None` once closed
+    pub(crate) depth: Option<usize>,
+}
+
+impl PhaseGuard<'_> {
+    /// Close the phase, and the phases still open inside it. `None` if it was already closed
+    /// because a phase it is nested in was.
+    pub fn finish(mut self) -> Option<Phase> {
+        let depth = self.depth.take()?;
+        self.benchmarker.close(depth)
+    }
+}
+
+impl Drop for PhaseGuard<'_> {
+    fn drop(&mut self) {
+        if let Some(depth) = self.depth.take() {
+            self.benchmarker.close(depth);
+        }
+    }
+}
+
+pub(crate) struct OpenPhase {
+    name: String,
+    start_time: Instant,
+    cpu_start: CpuTimes,
+    energy_start: EnergyReading,
+    pub memory: MemoryMeter,
+    pub phases: Vec<Phase>,
+}
+
+impl OpenPhase {
+    pub fn start(name: &str, sample_interval: Duration, energy: &EnergyMeter) -> OpenPhase {
+        let memory = MemoryMeter::start(sample_interval);
+        OpenPhase {
+            name: name.to_string(),
+            cpu_start: CpuTimes::now(),
+            energy_start: energy.read(),
+            start_time: Instant::now(),
+            memory,
+            phases: Vec::new(),
+        }
+    }
+
+    pub fn close(self, energy: &EnergyMeter) -> Phase {
+        let wall_time = self.start_time.elapsed().as_secs_f64();
+        let cpu = CpuTimes::now().since(&self.cpu_start);
+        let energy = energy.since(&self.energy_start, cpu.user + cpu.system);
+        let peaks = self.memory.stop();
+        let cpu_time = (cpu.user + cpu.system).as_secs_f64();
+        Phase {
+            name: self.name,
+            wall_time,
+            cpu_time,
+            user_time: cpu.user.as_secs_f64(),
+            system_time: cpu.system.as_secs_f64(),
+            threads: peaks.threads,
+            parallelism: if wall_time > 0.0 {
+                cpu_time / wall_time
+            } else {
+                0.0
+            },
+            peak_memory: peaks.process,
+            children_peak_memory: peaks.children,
+            energy,
+            phases: self.phases,
+        }
+    }
+}
+
+/// The CPU time of the process and of its children that exited, zero where `getrusage` is
+/// missing.
+#[derive(Default)]
+struct CpuTimes {
+    user: Duration,
+    system: Duration,
+}
+
+impl CpuTimes {
+    fn now() -> CpuTimes {
+        [Who::Process, Who::Children]
+            .into_iter()
+            .filter_map(resource_usage)
+            .fold(CpuTimes::default(), |times, usage| CpuTimes {
+                user: times.user + usage.user_time,
+                system: times.system + usage.system_time,
+            })
+    }
+
+    fn since(&self, start: &CpuTimes) -> CpuTimes {
+        CpuTimes {
+            user: self.user.saturating_sub(start.user),
+            system: self.system.saturating_sub(start.system),
+        }
+    }
+}
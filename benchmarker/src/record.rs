@@ -0,0 +1,537 @@
+//! Versioned, machine-readable result files.
+//!
+//! Every run is written as one JSON [`RunRecord`] and appended to a long-format CSV file with one
+//! row per metric, so new metrics never change the CSV header. Bump [`SCHEMA_VERSION`] whenever a
+//! field is renamed or its meaning changes.
+
+use crate::phase::Phase;
+use crate::results::{
+    BenchmarkResult, ExecutionResult, Metric, NativeResult, OutputCheck, PhaseStatistics,
+    RunCounts, Statistic, Unit,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const SCHEMA_VERSION: u32 = 7;
+
+const CSV_HEADER: &str = "schema_version,timestamp,zkvm,sdk_version,test_name,proof_kind,inputs,\
+host_cpu,host_cores,host_memory_mib,warmup_runs,measured_runs,output_check,metric,value,unit";
+
+/// A generator call whose result was written to the guest, e.g. `input2 = other_input(7)`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InputParameter {
+    pub name: String,
+    pub value: String,
+}
+
+/// The machine the benchmark ran on.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HostInfo {
+    pub hostname: Option<String>,
+    pub os: String,
+    pub kernel: Option<String>,
+    pub arch: String,
+    pub cpu_model: Option<String>,
+    pub cpu_cores: usize,
+    pub total_memory: Option<Metric>,
+}
+
+impl HostInfo {
+    /// Describe the current machine. Fields that cannot be read are left empty.
+    pub fn collect() -> HostInfo {
+        let read_trimmed = |path: &str| {
+            fs::read_to_string(path)
+                .ok()
+                .map(|content| content.trim().to_string())
+        };
+        let cpu_model = fs::read_to_string("/proc/cpuinfo")
+            .ok()
+            .and_then(|cpuinfo| {
+                cpuinfo
+                    .lines()
+                    .find(|line| line.starts_with("model name"))
+                    .and_then(|line| line.split_once(':'))
+                    .map(|(_, model)| model.trim().to_string())
+            });
+        let total_memory = fs::read_to_string("/proc/meminfo")
+            .ok()
+            .and_then(|meminfo| {
+                meminfo
+                    .lines()
+                    .find_map(|line| line.strip_prefix("MemTotal:"))
+                    .and_then(|value| {
+                        value
+                            .trim()
+                            .trim_end_matches("kB")
+                            .trim()
+                            .parse::<f64>()
+                            .ok()
+                    })
+                    .map(|kib| Metric::new(kib / 1024.0, Unit::Mebibytes))
+            });
+
+        HostInfo {
+            hostname: read_trimmed("/proc/sys/kernel/hostname"),
+            os: std::env::consts::OS.to_string(),
+            kernel: read_trimmed("/proc/sys/kernel/osrelease"),
+            arch: std::env::consts::ARCH.to_string(),
+            cpu_model,
+            cpu_cores: std::thread::available_parallelism().map_or(1, |cores| cores.get()),
+            total_memory,
+        }
+    }
+}
+
+/// One benchmark run of one test on one zkVM (or the native baseline).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub schema_version: u32,
+    /// Seconds since the Unix epoch at which the record was written
+    pub timestamp: u64,
+    pub zkvm: String,
+    pub sdk_version: Option<String>,
+    pub test_name: String,
+    /// The kind of proof that was measured, empty for native and execute-only runs
+    pub proof_kind: Option<String>,
+    pub inputs: Vec<InputParameter>,
+    pub host: HostInfo,
+    pub warmup_runs: usize,
+    pub measured_runs: usize,
+    /// Repeated metrics are stored as their mean under the plain name, with `_median`, `_stddev`,
+    /// `_min`, `_max`, `_ci95_low` and `_ci95_high` variants next to it
+    pub metrics: BTreeMap<String, Metric>,
+    /// Committed outputs as printed by the host, in commit order
+    pub outputs: Vec<String>,
+    /// Whether the outputs matched the template's `expected()`, empty if it declares none
+    pub output_check: Option<OutputCheck>,
+    /// The tree of phases the host measured, only written to the JSON file
+    #[serde(default)]
+    pub phases: Vec<Phase>,
+}
+
+impl RunRecord {
+    pub fn new(zkvm: &str, test_name: &str, sdk_version: Option<String>) -> RunRecord {
+        RunRecord {
+            schema_version: SCHEMA_VERSION,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs()),
+            zkvm: zkvm.to_string(),
+            sdk_version,
+            test_name: test_name.to_string(),
+            proof_kind: None,
+            inputs: Vec::new(),
+            host: HostInfo::collect(),
+            warmup_runs: 0,
+            measured_runs: 1,
+            metrics: BTreeMap::new(),
+            outputs: Vec::new(),
+            output_check: None,
+            phases: Vec::new(),
+        }
+    }
+
+    pub fn add_metric(&mut self, name: &str, metric: Option<Metric>) {
+        if let Some(metric) = metric {
+            self.metrics.insert(name.to_string(), metric);
+        }
+    }
+
+    /// Add the mean of a repeated metric and its spread.
+    pub fn add_statistic(&mut self, name: &str, statistic: &Statistic) {
+        let summary = &statistic.summary;
+        let fields = [
+            ("", summary.mean),
+            ("_median", summary.median),
+            ("_stddev", summary.stddev),
+            ("_min", summary.min),
+            ("_max", summary.max),
+            ("_ci95_low", summary.ci95_low),
+            ("_ci95_high", summary.ci95_high),
+        ];
+        for (suffix, value) in fields {
+            let name = format!("{}{}", name, suffix);
+            self.add_metric(&name, Some(Metric::new(value, statistic.unit)));
+        }
+    }
+
+    /// Add the statistics of a phase as `<phase>_time`, `<phase>_peak_memory`, ... An estimated
+    /// energy is added as `<phase>_estimated_energy`, so it is never mistaken for a measured one.
+    pub fn add_phase(&mut self, phase: &str, statistics: &PhaseStatistics) {
+        let energy = if statistics.energy_estimated {
+            "estimated_energy"
+        } else {
+            "energy"
+        };
+        let statistics = [
+            ("time", Some(&statistics.time)),
+            ("user_time", Some(&statistics.user_time)),
+            ("system_time", Some(&statistics.system_time)),
+            ("threads", Some(&statistics.threads)),
+            ("parallelism", Some(&statistics.parallelism)),
+            (energy, Some(&statistics.energy)),
+            ("peak_memory", Some(&statistics.peak_memory)),
+            (
+                "children_peak_memory",
+                statistics.children_peak_memory.as_ref(),
+            ),
+        ];
+        for (metric, statistic) in statistics {
+            if let Some(statistic) = statistic {
+                self.add_statistic(&format!("{}_{}", phase, metric), statistic);
+            }
+        }
+    }
+
+    fn set_runs(&mut self, runs: RunCounts) {
+        self.warmup_runs = runs.warmup;
+        self.measured_runs = runs.measured;
+    }
+
+    pub fn with_benchmark(mut self, result: &BenchmarkResult) -> RunRecord {
+        self.set_runs(result.runs);
+        self.proof_kind = result.proof_kind.clone();
+        self.output_check = result.output_check;
+        self.add_metric("total_cycles", result.total_cycles);
+        if let Some(setup) = &result.setup {
+            self.add_phase("setup", setup);
+        }
+        self.add_phase("proving", &result.proving);
+        self.add_metric("proof_size", Some(result.proof_size));
+        self.add_phase("verification", &result.verification);
+        self.add_metric("native_slowdown", result.native_slowdown);
+        self.phases = result.phases.clone();
+        self
+    }
+
+    pub fn with_native(mut self, result: &NativeResult) -> RunRecord {
+        self.set_runs(result.runs);
+        self.add_phase("execution", &result.execution);
+        self.add_metric("instructions_retired", result.instructions_retired);
+        self.outputs = result.committed_outputs.clone();
+        self.output_check = result.output_check;
+        self.phases = result.phases.clone();
+        self
+    }
+
+    pub fn with_execution(mut self, result: &ExecutionResult) -> RunRecord {
+        self.set_runs(result.runs);
+        self.add_phase("execution", &result.execution);
+        self.add_metric("total_cycles", Some(result.total_cycles));
+        self.add_metric("user_cycles", result.user_cycles);
+        self.add_metric("paging_cycles", result.paging_cycles);
+        self.add_metric("segments", result.segments);
+        self.add_metric("syscalls", result.syscalls);
+        self.phases = result.phases.clone();
+        self
+    }
+
+    pub fn metric(&self, name: &str) -> Option<Metric> {
+        self.metrics.get(name).copied()
+    }
+
+    pub fn write_json(&self, path: &Path) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(path, json + "\n")
+    }
+
+    pub fn read_json(path: &Path) -> io::Result<RunRecord> {
+        let json = fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(io::Error::other)
+    }
+
+    /// Append one row per metric, writing the header first if the file is new or empty.
+    pub fn append_csv(&self, path: &Path) -> io::Result<()> {
+        let needs_header = fs::metadata(path).map_or(true, |metadata| metadata.len() == 0);
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if needs_header {
+            writeln!(file, "{}", CSV_HEADER)?;
+        }
+
+        let inputs = self
+            .inputs
+            .iter()
+            .map(|input| format!("{}={}", input.name, input.value))
+            .collect::<Vec<_>>()
+            .join(";");
+        let memory = self
+            .host
+            .total_memory
+            .map(|memory| memory.value.to_string())
+            .unwrap_or_default();
+        let output_check = match self.output_check {
+            Some(OutputCheck::Passed) => "passed",
+            Some(OutputCheck::Failed) => "failed",
+            None => "",
+        };
+        for (name, metric) in &self.metrics {
+            let row = [
+                self.schema_version.to_string(),
+                self.timestamp.to_string(),
+                self.zkvm.clone(),
+                self.sdk_version.clone().unwrap_or_default(),
+                self.test_name.clone(),
+                self.proof_kind.clone().unwrap_or_default(),
+                inputs.clone(),
+                self.host.cpu_model.clone().unwrap_or_default(),
+                self.host.cpu_cores.to_string(),
+                memory.clone(),
+                self.warmup_runs.to_string(),
+                self.measured_runs.to_string(),
+                output_check.to_string(),
+                name.clone(),
+                metric.value.to_string(),
+                metric.unit.to_string(),
+            ];
+            let row: Vec<String> = row.iter().map(|field| csv_escape(field)).collect();
+            writeln!(file, "{}", row.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse the `Input parameter: name = value` lines printed by the generated host code.
+pub fn parse_input_parameters(bench_log: &str) -> Vec<InputParameter> {
+    bench_log
+        .lines()
+        .filter_map(|line| line.strip_prefix("Input parameter:"))
+        .filter_map(|parameter| parameter.split_once('='))
+        .map(|(name, value)| InputParameter {
+            name: name.trim().to_string(),
+            value: value.trim().to_string(),
+        })
+        .collect()
+}
+
+/// Find the version of `package` in a `Cargo.lock` file. If the lockfile has several, the one a
+/// workspace member depends on is taken; if that does not single one out, all of them are listed.
+pub fn locked_version(lockfile: &str, package: &str) -> Option<String> {
+    let packages: Vec<LockedPackage> = lockfile
+        .split("[[package]]")
+        .skip(1)
+        .map(LockedPackage::parse)
+        .collect();
+    let versions: Vec<&str> = packages
+        .iter()
+        .filter(|locked| locked.name == package)
+        .map(|locked| locked.version)
+        .collect();
+    if versions.len() < 2 {
+        return versions.first().map(|version| version.to_string());
+    }
+
+    // a dependency is written as `name version` when more than one version is locked
+    let direct: Vec<&str> = versions
+        .iter()
+        .copied()
+        .filter(|version| {
+            let dependency = format!("{} {}", package, version);
+            packages
+                .iter()
+                .filter(|locked| locked.is_workspace_member)
+                .flat_map(|locked| &locked.dependencies)
+                .any(|name| *name == dependency || name.starts_with(&format!("{} (", dependency)))
+        })
+        .collect();
+    match direct.as_slice() {
+        [version] => Some(version.to_string()),
+        _ => Some(versions.join(", ")),
+    }
+}
+
+/// One `[[package]]` entry of a `Cargo.lock` file.
+struct LockedPackage<'a> {
+    name: &'a str,
+    version: &'a str,
+    /// Workspace members are the packages without a `source`
+    is_workspace_member: bool,
+    dependencies: Vec<&'a str>,
+}
+
+impl<'a> LockedPackage<'a> {
+    fn parse(entry: &'a str) -> LockedPackage<'a> {
+        let value = |key: &str| {
+            entry.lines().find_map(|line| {
+                let (name, value) = line.split_once('=')?;
+                (name.trim() == key).then(|| value.trim().trim_matches('"'))
+            })
+        };
+        let dependencies = entry
+            .split_once("dependencies = [")
+            .and_then(|(_, rest)| rest.split_once(']'))
+            .map(|(list, _)| {
+                list.split(',')
+                    .map(|name| name.trim().trim_matches('"'))
+                    .filter(|name| !name.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        LockedPackage {
+            name: value("name").unwrap_or_default(),
+            version: value("version").unwrap_or_default(),
+            is_workspace_member: value("source").is_none(),
+            dependencies,
+        }
+    }
+}
+
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::results::Metric;
+
+    /// A file of this test run in the temporary directory, removed if it exists.
+    fn temp_file(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "benchmarker-record-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn record() -> RunRecord {
+        let mut record = RunRecord::new("risc0", "fibonacci", Some("1.1.2".to_string()));
+        record.add_metric("proof_size", Some(Metric::new(217.5, Unit::Kilobytes)));
+        record.add_metric("total_cycles", Some(Metric::new(65536.0, Unit::Cycles)));
+        record
+    }
+
+    #[test]
+    fn writes_the_csv_header_once() {
+        let path = temp_file("header.csv");
+        record().append_csv(&path).unwrap();
+        record().append_csv(&path).unwrap();
+
+        let csv = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(lines.iter().filter(|line| **line == CSV_HEADER).count(), 1);
+        assert!(lines[1].ends_with(",proof_size,217.5,KB"), "{}", lines[1]);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn escapes_csv_fields() {
+        assert_eq!(csv_escape("plain"), "plain");
+        assert_eq!(csv_escape("a,b"), "\"a,b\"");
+        assert_eq!(csv_escape("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_escape("two\nlines"), "\"two\nlines\"");
+
+        let path = temp_file("escape.csv");
+        let mut record = record();
+        record.inputs = parse_input_parameters("Input parameter: input1 = vec![1, 2]\n");
+        record.append_csv(&path).unwrap();
+        let csv = fs::read_to_string(&path).unwrap();
+        assert!(csv.contains(",\"input1=vec![1, 2]\","), "{}", csv);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn round_trips_through_json() {
+        let path = temp_file("record.json");
+        let mut record = record();
+        record.inputs = parse_input_parameters("Input parameter: input1 = input()\n");
+        record.outputs = vec!["3700000000000000".to_string()];
+        record.output_check = Some(OutputCheck::Passed);
+        record.write_json(&path).unwrap();
+
+        assert_eq!(RunRecord::read_json(&path).unwrap(), record);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn parses_input_parameters() {
+        let log = "Warmup runs: 0\nInput parameter: input1 = input()\nInput parameter: n = other_input(7)\n";
+        assert_eq!(
+            parse_input_parameters(log),
+            [
+                InputParameter {
+                    name: "input1".to_string(),
+                    value: "input()".to_string(),
+                },
+                InputParameter {
+                    name: "n".to_string(),
+                    value: "other_input(7)".to_string(),
+                },
+            ]
+        );
+    }
+
+    const LOCKFILE: &str = r#"# This file is automatically @generated by Cargo.
+version = 3
+
+[[package]]
+name = "host"
+version = "0.1.0"
+dependencies = [
+ "methods",
+ "risc0-zkvm 1.1.2",
+ "serde",
+]
+
+[[package]]
+name = "methods"
+version = "0.1.0"
+dependencies = [
+ "risc0-zkvm 1.0.5",
+]
+
+[[package]]
+name = "risc0-zkvm"
+version = "1.0.5"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "risc0-zkvm"
+version = "1.1.2"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+dependencies = [
+ "serde",
+]
+
+[[package]]
+name = "serde"
+version = "1.0.210"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#;
+
+    #[test]
+    fn finds_a_locked_version() {
+        assert_eq!(
+            locked_version(LOCKFILE, "serde"),
+            Some("1.0.210".to_string())
+        );
+        assert_eq!(locked_version(LOCKFILE, "sp1-sdk"), None);
+    }
+
+    #[test]
+    fn lists_every_version_of_a_package_locked_twice() {
+        // both versions are direct dependencies of a workspace member
+        assert_eq!(
+            locked_version(LOCKFILE, "risc0-zkvm"),
+            Some("1.0.5, 1.1.2".to_string())
+        );
+
+        // only one of them is once the guest methods depend on it through another crate
+        let lockfile = LOCKFILE.replace(" \"risc0-zkvm 1.0.5\",\n", "");
+        assert_eq!(
+            locked_version(&lockfile, "risc0-zkvm"),
+            Some("1.1.2".to_string())
+        );
+    }
+}
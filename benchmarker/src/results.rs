@@ -0,0 +1,854 @@
+//! Typed benchmark results parsed from the host program logs.
+//!
+//! The host templates print one line per metric (`Proving time: 1.2s`, `Proof size: 12.3 KB`,
+//! ...) and the zkVM SDKs log the cycle count. Every metric is parsed into a number with an
+//! explicit [`Unit`]; a missing or malformed line is an error instead of an empty field in the
+//! result file.
+
+use crate::phase::Phase;
+use crate::stats::Summary;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+/// Unit of a [`Metric`], serialized as the same symbol that `Display` prints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Unit {
+    #[serde(rename = "cycles")]
+    Cycles,
+    #[serde(rename = "instructions")]
+    Instructions,
+    #[serde(rename = "s")]
+    Seconds,
+    #[serde(rename = "KB")]
+    Kilobytes,
+    #[serde(rename = "MiB")]
+    Mebibytes,
+    #[serde(rename = "percent")]
+    Percent,
+    #[serde(rename = "J")]
+    Joules,
+    /// A unitless ratio, e.g. the slowdown relative to the native baseline
+    #[serde(rename = "ratio")]
+    Ratio,
+    /// A number of things, e.g. segments or syscalls
+    #[serde(rename = "count")]
+    Count,
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unit = match self {
+            Unit::Cycles => "cycles",
+            Unit::Instructions => "instructions",
+            Unit::Seconds => "s",
+            Unit::Kilobytes => "KB",
+            Unit::Mebibytes => "MiB",
+            Unit::Percent => "percent",
+            Unit::Joules => "J",
+            Unit::Ratio => "ratio",
+            Unit::Count => "count",
+        };
+        write!(f, "{}", unit)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Metric {
+    pub value: f64,
+    pub unit: Unit,
+}
+
+impl Metric {
+    pub fn new(value: f64, unit: Unit) -> Metric {
+        Metric { value, unit }
+    }
+}
+
+impl fmt::Display for Metric {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.value, self.unit)
+    }
+}
+
+/// A metric measured over the repeated runs of a phase.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Statistic {
+    pub unit: Unit,
+    #[serde(flatten)]
+    pub summary: Summary,
+}
+
+impl Statistic {
+    fn from_samples(samples: &[f64], unit: Unit) -> Option<Statistic> {
+        Summary::from_samples(samples).map(|summary| Statistic { unit, summary })
+    }
+
+    pub fn mean(&self) -> Metric {
+        Metric::new(self.summary.mean, self.unit)
+    }
+}
+
+/// Whether the committed outputs matched the template's `expected()` values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputCheck {
+    Passed,
+    Failed,
+}
+
+/// How many times each phase ran, as printed by `RunConfig::print`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunCounts {
+    pub warmup: usize,
+    pub measured: usize,
+}
+
+/// The measured runs of one phase, as printed by `Measurements::report`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PhaseStatistics {
+    pub time: Statistic,
+    /// CPU time of the host process and of the child processes that exited during the phase
+    pub user_time: Statistic,
+    pub system_time: Statistic,
+    /// The most threads of the host and its child processes running at once
+    pub threads: Statistic,
+    /// CPU time over wall time, the number of cores kept busy on average
+    pub parallelism: Statistic,
+    /// Energy of the whole CPU during the phase, measured with RAPL
+    pub energy: Statistic,
+    /// Whether the energy was estimated from the CPU time because RAPL was unavailable
+    pub energy_estimated: bool,
+    /// Peak resident memory of the host process
+    pub peak_memory: Statistic,
+    /// Peak resident memory of the processes the host started, for provers run as a separate
+    /// program
+    pub children_peak_memory: Option<Statistic>,
+}
+
+impl PhaseStatistics {
+    /// The mean CPU time of a run.
+    pub fn cpu_time(&self) -> Metric {
+        Metric::new(
+            self.user_time.summary.mean + self.system_time.summary.mean,
+            Unit::Seconds,
+        )
+    }
+}
+
+/// The metrics of one zkVM benchmark run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchmarkResult {
+    pub runs: RunCounts,
+    /// The `Proof kind:` line, printed by the hosts that support more than one kind of proof
+    pub proof_kind: Option<String>,
+    pub total_cycles: Option<Metric>,
+    /// Key generation, measured by the hosts that have a separate setup step
+    pub setup: Option<PhaseStatistics>,
+    pub proving: PhaseStatistics,
+    pub proof_size: Metric,
+    pub verification: PhaseStatistics,
+    pub native_slowdown: Option<Metric>,
+    /// `None` when the template declares no expected outputs
+    pub output_check: Option<OutputCheck>,
+    /// The phases the host measured, empty if it printed none
+    pub phases: Vec<Phase>,
+}
+
+/// The metrics of one native baseline run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NativeResult {
+    pub runs: RunCounts,
+    pub execution: PhaseStatistics,
+    pub instructions_retired: Option<Metric>,
+    pub committed_outputs: Vec<String>,
+    /// `None` when the template declares no expected outputs
+    pub output_check: Option<OutputCheck>,
+    /// The phases the host measured, empty if it printed none
+    pub phases: Vec<Phase>,
+}
+
+/// The metrics of an execute-only zkVM run, which reports cycles without proving.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExecutionResult {
+    pub runs: RunCounts,
+    pub execution: PhaseStatistics,
+    pub total_cycles: Metric,
+    /// The counts below are only reported by the zkVMs that have them
+    pub user_cycles: Option<Metric>,
+    pub paging_cycles: Option<Metric>,
+    pub segments: Option<Metric>,
+    pub syscalls: Option<Metric>,
+    pub phases: Vec<Phase>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    /// Required lines that never appeared in the log
+    MissingFields(Vec<String>),
+    InvalidValue {
+        field: String,
+        value: String,
+        reason: String,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::MissingFields(fields) => {
+                write!(f, "missing fields in benchmark logs: {}", fields.join(", "))
+            }
+            ParseError::InvalidValue {
+                field,
+                value,
+                reason,
+            } => write!(f, "invalid value {:?} for {}: {}", value, field, reason),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Which metrics the zkVM is expected to report.
+#[derive(Debug, Clone, Copy)]
+pub struct ParseOptions {
+    /// Valida does not report a cycle count, RISC Zero and SP1 always do
+    pub require_cycles: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            require_cycles: true,
+        }
+    }
+}
+
+/// Collects the names of missing fields so they are all reported at once.
+#[derive(Default)]
+struct Missing(Vec<String>);
+
+impl Missing {
+    fn check<T>(&mut self, field: &str, value: Option<T>) -> Option<T> {
+        if value.is_none() {
+            self.0.push(field.to_string());
+        }
+        value
+    }
+
+    fn into_result(self) -> Result<(), ParseError> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(ParseError::MissingFields(self.0))
+        }
+    }
+}
+
+impl BenchmarkResult {
+    /// Build a result from the host program log.
+    pub fn from_log(
+        bench_log: &str,
+        native_execution_time: Option<Metric>,
+        options: ParseOptions,
+    ) -> Result<BenchmarkResult, ParseError> {
+        let mut missing = Missing::default();
+
+        let bench_log = strip_ansi_escapes(bench_log);
+        let bench_log = bench_log.as_str();
+        let runs = parse_run_counts(bench_log)?;
+        let proof_kind = find_value(bench_log, "Proof kind:").map(str::to_string);
+        let total_cycles = parse_total_cycles(bench_log)?;
+        if options.require_cycles {
+            missing.check("total cycles", total_cycles);
+        }
+        let setup = parse_phase(bench_log, "Setup", runs, false, &mut missing)?;
+        let proving = parse_phase(bench_log, "Proving", runs, true, &mut missing)?;
+        let proof_size = find_value(bench_log, "Proof size:")
+            .map(|value| parse_kilobytes("proof size", value))
+            .transpose()?;
+        let verification = parse_phase(bench_log, "Verification", runs, true, &mut missing)?;
+        let output_check = parse_output_check(bench_log)?;
+        let phases = parse_phases(bench_log)?;
+
+        let runs = missing.check("warmup and measured runs", runs);
+        let proof_size = missing.check("proof size", proof_size);
+        missing.into_result()?;
+
+        let proving = proving.unwrap();
+        let native_slowdown = native_execution_time
+            .filter(|native| native.value > 0.0)
+            .map(|native| Metric::new(proving.time.summary.mean / native.value, Unit::Ratio));
+
+        Ok(BenchmarkResult {
+            runs: runs.unwrap(),
+            proof_kind,
+            total_cycles: total_cycles.map(|cycles| Metric::new(cycles as f64, Unit::Cycles)),
+            setup,
+            proving,
+            proof_size: Metric::new(proof_size.unwrap(), Unit::Kilobytes),
+            verification: verification.unwrap(),
+            native_slowdown,
+            output_check,
+            phases,
+        })
+    }
+}
+
+impl NativeResult {
+    /// Build a result from the log of the native host program.
+    pub fn from_log(bench_log: &str) -> Result<NativeResult, ParseError> {
+        let mut missing = Missing::default();
+
+        let bench_log = strip_ansi_escapes(bench_log);
+        let bench_log = bench_log.as_str();
+        let runs = parse_run_counts(bench_log)?;
+        let execution = parse_phase(bench_log, "Execution", runs, true, &mut missing)?;
+        // the line is always printed, with "unavailable" when there is no PMU
+        let instructions_retired = match find_value(bench_log, "Instructions retired:") {
+            None => {
+                missing.0.push("instructions retired".to_string());
+                None
+            }
+            Some("unavailable") => None,
+            Some(value) => Some(Metric::new(
+                parse_integer("instructions retired", value)? as f64,
+                Unit::Instructions,
+            )),
+        };
+        let committed_outputs = bench_log
+            .lines()
+            .filter_map(|line| line.strip_prefix("Committed output:"))
+            .map(|value| value.trim().to_string())
+            .collect();
+        let output_check = parse_output_check(bench_log)?;
+        let phases = parse_phases(bench_log)?;
+
+        let runs = missing.check("warmup and measured runs", runs);
+        missing.into_result()?;
+
+        Ok(NativeResult {
+            runs: runs.unwrap(),
+            execution: execution.unwrap(),
+            instructions_retired,
+            committed_outputs,
+            output_check,
+            phases,
+        })
+    }
+}
+
+impl ExecutionResult {
+    /// Build a result from the log of a host program run with `BENCH_MODE=execute`.
+    pub fn from_log(bench_log: &str) -> Result<ExecutionResult, ParseError> {
+        let mut missing = Missing::default();
+
+        let bench_log = strip_ansi_escapes(bench_log);
+        let bench_log = bench_log.as_str();
+        let runs = parse_run_counts(bench_log)?;
+        let execution = parse_phase(bench_log, "Execution", runs, true, &mut missing)?;
+        let count = |field: &str, prefix: &str, unit: Unit| {
+            find_value(bench_log, prefix)
+                .map(|value| parse_integer(field, value).map(|n| Metric::new(n as f64, unit)))
+                .transpose()
+        };
+        let total_cycles = count("total cycles", "Total cycles:", Unit::Cycles)?;
+        let user_cycles = count("user cycles", "User cycles:", Unit::Cycles)?;
+        let paging_cycles = count("paging cycles", "Paging cycles:", Unit::Cycles)?;
+        let segments = count("segments", "Segments:", Unit::Count)?;
+        let syscalls = count("syscalls", "Syscalls:", Unit::Count)?;
+        let phases = parse_phases(bench_log)?;
+
+        let runs = missing.check("warmup and measured runs", runs);
+        let total_cycles = missing.check("total cycles", total_cycles);
+        missing.into_result()?;
+
+        Ok(ExecutionResult {
+            runs: runs.unwrap(),
+            execution: execution.unwrap(),
+            total_cycles: total_cycles.unwrap(),
+            user_cycles,
+            paging_cycles,
+            segments,
+            syscalls,
+            phases,
+        })
+    }
+}
+
+fn parse_output_check(log: &str) -> Result<Option<OutputCheck>, ParseError> {
+    match find_value(log, "Output check:") {
+        None => Ok(None),
+        Some("passed") => Ok(Some(OutputCheck::Passed)),
+        Some("failed") => Ok(Some(OutputCheck::Failed)),
+        Some(value) => Err(ParseError::InvalidValue {
+            field: "output check".to_string(),
+            value: value.to_string(),
+            reason: "expected passed or failed".to_string(),
+        }),
+    }
+}
+
+/// Parse the `Phases: [...]` line printed by `Benchmarker::report_phases`.
+fn parse_phases(log: &str) -> Result<Vec<Phase>, ParseError> {
+    let Some(value) = find_value(log, "Phases:") else {
+        return Ok(Vec::new());
+    };
+    serde_json::from_str(value).map_err(|err| ParseError::InvalidValue {
+        field: "phases".to_string(),
+        value: value.to_string(),
+        reason: err.to_string(),
+    })
+}
+
+fn parse_run_counts(log: &str) -> Result<Option<RunCounts>, ParseError> {
+    let warmup = find_value(log, "Warmup runs:")
+        .map(|value| parse_integer("warmup runs", value))
+        .transpose()?;
+    let measured = find_value(log, "Measured runs:")
+        .map(|value| parse_integer("measured runs", value))
+        .transpose()?;
+    Ok(warmup.zip(measured).map(|(warmup, measured)| RunCounts {
+        warmup: warmup as usize,
+        measured: measured as usize,
+    }))
+}
+
+/// Parse the lines `Measurements::report` prints for `phase`, e.g. `Proving time: ...`. A phase
+/// that is not `required` is `None` if none of its lines were printed; otherwise every line but
+/// the children peak memory has to be there.
+fn parse_phase(
+    log: &str,
+    phase: &str,
+    runs: Option<RunCounts>,
+    required: bool,
+    missing: &mut Missing,
+) -> Result<Option<PhaseStatistics>, ParseError> {
+    let name = phase.to_lowercase();
+    let field = |metric: &str| format!("{} {}", name, metric);
+    let prefix = |metric: &str| format!("{} {}:", phase, metric);
+    let durations = |metric: &str| parse_time_samples(log, &field(metric), &prefix(metric), runs);
+    let memory = |metric: &str| parse_memory_samples(log, &field(metric), &prefix(metric), runs);
+    let numbers = |metric: &str, unit: Unit| {
+        let samples = parse_samples(log, &field(metric), &prefix(metric), runs, |value| {
+            parse_number(&field(metric), value)
+        })?;
+        Ok(Statistic::from_samples(&samples, unit))
+    };
+
+    let time = durations("time")?;
+    let user_time = durations("user time")?;
+    let system_time = durations("system time")?;
+    let threads = numbers("threads", Unit::Count)?;
+    let parallelism = numbers("parallelism", Unit::Ratio)?;
+    let energy = parse_energy_samples(log, &field("energy"), &prefix("energy"), runs)?;
+    let peak_memory = memory("peak memory")?;
+    let children_peak_memory = memory("children peak memory")?;
+    let measured = [
+        &time,
+        &user_time,
+        &system_time,
+        &threads,
+        &parallelism,
+        &energy.map(|(energy, _)| energy),
+        &peak_memory,
+    ]
+    .iter()
+    .any(|statistic| statistic.is_some());
+    if !required && !measured {
+        return Ok(None);
+    }
+
+    let time = missing.check(&field("time"), time);
+    let user_time = missing.check(&field("user time"), user_time);
+    let system_time = missing.check(&field("system time"), system_time);
+    let threads = missing.check(&field("threads"), threads);
+    let parallelism = missing.check(&field("parallelism"), parallelism);
+    let energy = missing.check(&field("energy"), energy);
+    let peak_memory = missing.check(&field("peak memory"), peak_memory);
+    let (
+        Some(time),
+        Some(user_time),
+        Some(system_time),
+        Some(threads),
+        Some(parallelism),
+        Some((energy, energy_estimated)),
+        Some(peak_memory),
+    ) = (
+        time,
+        user_time,
+        system_time,
+        threads,
+        parallelism,
+        energy,
+        peak_memory,
+    )
+    else {
+        return Ok(None);
+    };
+    Ok(Some(PhaseStatistics {
+        time,
+        user_time,
+        system_time,
+        threads,
+        parallelism,
+        energy,
+        energy_estimated,
+        peak_memory,
+        children_peak_memory,
+    }))
+}
+
+/// Parse every line starting with `prefix`, one per measured run.
+fn parse_samples(
+    log: &str,
+    field: &str,
+    prefix: &str,
+    runs: Option<RunCounts>,
+    parse: impl Fn(&str) -> Result<f64, ParseError>,
+) -> Result<Vec<f64>, ParseError> {
+    let samples = log
+        .lines()
+        .filter_map(|line| line.strip_prefix(prefix))
+        .map(|value| parse(value.trim()))
+        .collect::<Result<Vec<_>, _>>()?;
+    if let Some(runs) = runs {
+        if !samples.is_empty() && samples.len() != runs.measured {
+            return Err(ParseError::InvalidValue {
+                field: field.to_string(),
+                value: samples.len().to_string(),
+                reason: format!("expected one sample per measured run ({})", runs.measured),
+            });
+        }
+    }
+    Ok(samples)
+}
+
+fn parse_time_samples(
+    log: &str,
+    field: &str,
+    prefix: &str,
+    runs: Option<RunCounts>,
+) -> Result<Option<Statistic>, ParseError> {
+    let samples = parse_samples(log, field, prefix, runs, |value| {
+        parse_duration(field, value).map(|duration| duration.as_secs_f64())
+    })?;
+    Ok(Statistic::from_samples(&samples, Unit::Seconds))
+}
+
+fn parse_memory_samples(
+    log: &str,
+    field: &str,
+    prefix: &str,
+    runs: Option<RunCounts>,
+) -> Result<Option<Statistic>, ParseError> {
+    let samples = parse_samples(log, field, prefix, runs, |value| {
+        parse_kilobytes(field, value)
+    })?;
+    Ok(Statistic::from_samples(&samples, Unit::Kilobytes))
+}
+
+/// Parse the energy samples, and whether any of them is an estimate.
+fn parse_energy_samples(
+    log: &str,
+    field: &str,
+    prefix: &str,
+    runs: Option<RunCounts>,
+) -> Result<Option<(Statistic, bool)>, ParseError> {
+    let estimated = log
+        .lines()
+        .filter(|line| line.starts_with(prefix))
+        .any(|line| line.trim_end().ends_with("(estimate)"));
+    let samples = parse_samples(log, field, prefix, runs, |value| parse_joules(field, value))?;
+    Ok(Statistic::from_samples(&samples, Unit::Joules).map(|energy| (energy, estimated)))
+}
+
+/// Remove the terminal color codes that `tracing` adds around field names and values.
+fn strip_ansi_escapes(log: &str) -> String {
+    let mut stripped = String::with_capacity(log.len());
+    let mut chars = log.chars();
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            // skip `ESC [ parameters final-byte`
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            stripped.push(c);
+        }
+    }
+    stripped
+}
+
+/// Return the text after the first line starting with `prefix`.
+fn find_value<'a>(log: &'a str, prefix: &str) -> Option<&'a str> {
+    log.lines()
+        .find_map(|line| line.strip_prefix(prefix))
+        .map(str::trim)
+}
+
+/// Parse the `Debug` representation of a `Duration`, e.g. `1.5s`, `20.3ms`, `4.1µs` or `900ns`.
+pub fn parse_duration(field: &str, value: &str) -> Result<Duration, ParseError> {
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let invalid = |reason: &str| ParseError::InvalidValue {
+        field: field.to_string(),
+        value: value.to_string(),
+        reason: reason.to_string(),
+    };
+    let number: f64 = number.parse().map_err(|_| invalid("not a number"))?;
+    let seconds = match unit {
+        "s" => number,
+        "ms" => number / 1e3,
+        "µs" | "us" => number / 1e6,
+        "ns" => number / 1e9,
+        _ => return Err(invalid("unknown duration unit")),
+    };
+    Ok(Duration::from_secs_f64(seconds))
+}
+
+/// Parse a size printed as `<number> KB`.
+fn parse_kilobytes(field: &str, value: &str) -> Result<f64, ParseError> {
+    let invalid = |reason: &str| ParseError::InvalidValue {
+        field: field.to_string(),
+        value: value.to_string(),
+        reason: reason.to_string(),
+    };
+    let number = value
+        .strip_suffix("KB")
+        .ok_or_else(|| invalid("expected a size in KB"))?;
+    number.trim().parse().map_err(|_| invalid("not a number"))
+}
+
+/// Parse an energy printed as `<number> J`, or `<number> J (estimate)`.
+fn parse_joules(field: &str, value: &str) -> Result<f64, ParseError> {
+    let invalid = |reason: &str| ParseError::InvalidValue {
+        field: field.to_string(),
+        value: value.to_string(),
+        reason: reason.to_string(),
+    };
+    let value = value.strip_suffix("(estimate)").unwrap_or(value).trim_end();
+    let number = value
+        .strip_suffix('J')
+        .ok_or_else(|| invalid("expected an energy in J"))?;
+    number.trim().parse().map_err(|_| invalid("not a number"))
+}
+
+fn parse_number(field: &str, value: &str) -> Result<f64, ParseError> {
+    value.parse().map_err(|_| ParseError::InvalidValue {
+        field: field.to_string(),
+        value: value.to_string(),
+        reason: "not a number".to_string(),
+    })
+}
+
+fn parse_integer(field: &str, value: &str) -> Result<u64, ParseError> {
+    value.parse().map_err(|_| ParseError::InvalidValue {
+        field: field.to_string(),
+        value: value.to_string(),
+        reason: "not an integer".to_string(),
+    })
+}
+
+/// RISC Zero logs `total cycles: N`, SP1 logs `... cycles=N`; the first occurrence wins.
+fn parse_total_cycles(log: &str) -> Result<Option<u64>, ParseError> {
+    for line in log.lines() {
+        let rest = match (line.find("total cycles:"), line.find(" cycles=")) {
+            (Some(start), _) => &line[start + "total cycles:".len()..],
+            (None, Some(start)) => &line[start + " cycles=".len()..],
+            (None, None) => continue,
+        };
+        let rest = rest.trim_start();
+        let digits = &rest[..rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len())];
+        return parse_integer("total cycles", digits).map(Some);
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Host output captured from the benchmarker, with the RISC Zero executor lines as the
+    // `tracing` subscriber of the host colours them.
+    const RISC0_LOG: &str = include_str!("../tests/logs/risc0_fibonacci.log");
+    const NATIVE_LOG: &str = include_str!("../tests/logs/native_fibonacci.log");
+
+    /// Wrap every line in colour codes, as a terminal-aware logger would.
+    fn colour(log: &str) -> String {
+        log.lines()
+            .map(|line| format!("\u{1b}[32m{}\u{1b}[0m\n", line))
+            .collect()
+    }
+
+    fn without_lines(log: &str, prefixes: &[&str]) -> String {
+        log.lines()
+            .filter(|line| !prefixes.iter().any(|prefix| line.starts_with(prefix)))
+            .map(|line| format!("{}\n", line))
+            .collect()
+    }
+
+    #[test]
+    fn parses_a_risc0_log() {
+        let native_time = Metric::new(0.0005, Unit::Seconds);
+        let result =
+            BenchmarkResult::from_log(RISC0_LOG, Some(native_time), ParseOptions::default())
+                .unwrap();
+
+        assert_eq!(
+            result.runs,
+            RunCounts {
+                warmup: 0,
+                measured: 1
+            }
+        );
+        assert_eq!(result.proof_kind.as_deref(), Some("succinct"));
+        assert_eq!(
+            result.total_cycles,
+            Some(Metric::new(65536.0, Unit::Cycles))
+        );
+        assert_eq!(result.setup, None);
+        assert_eq!(result.proving.time.unit, Unit::Seconds);
+        assert!((result.proving.time.summary.mean - 0.071909936).abs() < 1e-9);
+        assert!((result.proving.user_time.summary.mean - 0.001326).abs() < 1e-9);
+        assert_eq!(result.proving.peak_memory.summary.mean, 2616.0);
+        assert_eq!(result.proving.children_peak_memory, None);
+        assert_eq!(result.proving.energy.unit, Unit::Joules);
+        assert!(result.proving.energy_estimated);
+        assert_eq!(result.proof_size, Metric::new(217.5, Unit::Kilobytes));
+        assert!((result.verification.time.summary.mean - 0.002123139).abs() < 1e-9);
+        let slowdown = result.native_slowdown.unwrap();
+        assert!((slowdown.value - 0.071909936 / 0.0005).abs() < 1e-6);
+        assert_eq!(result.output_check, Some(OutputCheck::Passed));
+
+        let names: Vec<&str> = result
+            .phases
+            .iter()
+            .map(|phase| phase.name.as_str())
+            .collect();
+        assert_eq!(names, ["setup", "prove", "verify"]);
+        let nested: Vec<&str> = result.phases[1]
+            .phases
+            .iter()
+            .map(|phase| phase.name.as_str())
+            .collect();
+        assert_eq!(nested, ["composite", "compress"]);
+    }
+
+    #[test]
+    fn parses_a_native_log() {
+        let result = NativeResult::from_log(NATIVE_LOG).unwrap();
+
+        assert_eq!(result.runs.measured, 2);
+        assert_eq!(result.execution.time.summary.samples, 2);
+        assert!((result.execution.time.summary.min - 0.000015151).abs() < 1e-12);
+        assert!((result.execution.time.summary.max - 0.000020083).abs() < 1e-12);
+        assert_eq!(result.execution.threads.summary.mean, 1.0);
+        assert!((result.execution.energy.summary.mean - 0.00156).abs() < 1e-9);
+        assert!(result.execution.energy_estimated);
+        assert_eq!(result.instructions_retired, None);
+        assert_eq!(result.committed_outputs, ["3700000000000000"]);
+        assert_eq!(result.output_check, Some(OutputCheck::Passed));
+        assert_eq!(result.phases.len(), 2);
+    }
+
+    #[test]
+    fn colours_do_not_change_the_result() {
+        assert_eq!(
+            NativeResult::from_log(&colour(NATIVE_LOG)),
+            NativeResult::from_log(NATIVE_LOG)
+        );
+        assert_eq!(
+            BenchmarkResult::from_log(&colour(RISC0_LOG), None, ParseOptions::default()),
+            BenchmarkResult::from_log(RISC0_LOG, None, ParseOptions::default())
+        );
+    }
+
+    #[test]
+    fn reports_every_missing_field() {
+        let log = without_lines(RISC0_LOG, &["Proof size:", "Verification energy:"]);
+        let err = BenchmarkResult::from_log(&log, None, ParseOptions::default()).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::MissingFields(vec![
+                "verification energy".to_string(),
+                "proof size".to_string()
+            ])
+        );
+
+        let log = without_lines(NATIVE_LOG, &["Instructions retired:"]);
+        let err = NativeResult::from_log(&log).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::MissingFields(vec!["instructions retired".to_string()])
+        );
+    }
+
+    #[test]
+    fn requires_cycles_only_when_asked() {
+        let log = without_lines(RISC0_LOG, &["\u{1b}[2m"]);
+        let err = BenchmarkResult::from_log(&log, None, ParseOptions::default()).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::MissingFields(vec!["total cycles".to_string()])
+        );
+
+        let options = ParseOptions {
+            require_cycles: false,
+        };
+        let result = BenchmarkResult::from_log(&log, None, options).unwrap();
+        assert_eq!(result.total_cycles, None);
+    }
+
+    #[test]
+    fn rejects_a_sample_per_run_mismatch() {
+        let log = NATIVE_LOG.replace("Measured runs: 2", "Measured runs: 3");
+        let err = NativeResult::from_log(&log).unwrap_err();
+        assert!(
+            matches!(&err, ParseError::InvalidValue { field, .. } if field == "execution time"),
+            "{:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn rejects_a_malformed_value() {
+        let log = RISC0_LOG.replace("Proof size: 217.5 KB", "Proof size: 217.5 MB");
+        let err = BenchmarkResult::from_log(&log, None, ParseOptions::default()).unwrap_err();
+        assert_eq!(
+            err,
+            ParseError::InvalidValue {
+                field: "proof size".to_string(),
+                value: "217.5 MB".to_string(),
+                reason: "expected a size in KB".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn parses_durations_in_every_unit() {
+        let parse = |value| parse_duration("time", value).unwrap();
+        assert_eq!(parse("1.5s"), Duration::from_millis(1500));
+        assert_eq!(parse("20.5ms"), Duration::from_micros(20500));
+        assert_eq!(parse("4µs"), Duration::from_micros(4));
+        assert_eq!(parse("4us"), Duration::from_micros(4));
+        assert_eq!(parse("900ns"), Duration::from_nanos(900));
+        assert!(parse_duration("time", "3 minutes").is_err());
+    }
+
+    #[test]
+    fn finds_the_cycles_of_either_sdk() {
+        let sp1 = "2026-10-18T06:12:04.118570Z  INFO execute: gas: 1234 cycles=8921\n";
+        assert_eq!(parse_total_cycles(sp1), Ok(Some(8921)));
+        let risc0 = strip_ansi_escapes(RISC0_LOG);
+        assert_eq!(parse_total_cycles(&risc0), Ok(Some(65536)));
+        assert_eq!(parse_total_cycles("Proving time: 1s\n"), Ok(None));
+    }
+}
@@ -0,0 +1,51 @@
+//! `getrusage` of the process and of its children, where the platform has it.
+
+use std::time::Duration;
+
+pub(crate) enum Who {
+    Process,
+    /// The children that exited and were waited for
+    Children,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct ResourceUsage {
+    pub user_time: Duration,
+    pub system_time: Duration,
+    /// Peak resident memory in KB over the whole life of the process or its children
+    pub max_rss: u64,
+}
+
+#[cfg(unix)]
+pub(crate) fn resource_usage(who: Who) -> Option<ResourceUsage> {
+    let who = match who {
+        Who::Process => libc::RUSAGE_SELF,
+        Who::Children => libc::RUSAGE_CHILDREN,
+    };
+    let mut rusage = std::mem::MaybeUninit::<libc::rusage>::zeroed();
+    // SAFETY: getrusage only writes the struct it is given
+    if unsafe { libc::getrusage(who, rusage.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    // SAFETY: the call succeeded, so the struct is initialized
+    let rusage = unsafe { rusage.assume_init() };
+    let time = |time: libc::timeval| {
+        Duration::new(time.tv_sec as u64, 0) + Duration::from_micros(time.tv_usec as u64)
+    };
+    let max_rss = rusage.ru_maxrss as u64;
+    Some(ResourceUsage {
+        user_time: time(rusage.ru_utime),
+        system_time: time(rusage.ru_stime),
+        // macOS reports bytes, Linux and the BSDs kilobytes
+        max_rss: if cfg!(target_os = "macos") {
+            max_rss / 1024
+        } else {
+            max_rss
+        },
+    })
+}
+
+#[cfg(not(unix))]
+pub(crate) fn resource_usage(_who: Who) -> Option<ResourceUsage> {
+    None
+}
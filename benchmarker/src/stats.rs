@@ -0,0 +1,152 @@
+//! Summary statistics over repeated measurements.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Two-sided 97.5% quantiles of Student's t distribution for 1 to 30 degrees of freedom.
+const T_QUANTILES: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+
+/// Normal approximation used above 30 degrees of freedom.
+const Z_QUANTILE: f64 = 1.960;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Summary {
+    pub samples: usize,
+    pub mean: f64,
+    pub median: f64,
+    /// Sample standard deviation (n - 1 in the denominator), 0 for a single sample
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+    /// 95% confidence interval of the mean
+    pub ci95_low: f64,
+    pub ci95_high: f64,
+}
+
+impl Summary {
+    /// Summarize a non-empty list of samples.
+    pub fn from_samples(samples: &[f64]) -> Option<Summary> {
+        if samples.is_empty() {
+            return None;
+        }
+        let n = samples.len();
+        let mean = samples.iter().sum::<f64>() / n as f64;
+
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        // `is_multiple_of` needs Rust 1.87, newer than the toolchain the SP1 host is pinned to
+        #[allow(clippy::manual_is_multiple_of)]
+        let median = if n % 2 == 0 {
+            (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0
+        } else {
+            sorted[n / 2]
+        };
+
+        let stddev = if n > 1 {
+            let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+            variance.sqrt()
+        } else {
+            0.0
+        };
+        let quantile = T_QUANTILES
+            .get(n.saturating_sub(2))
+            .copied()
+            .unwrap_or(Z_QUANTILE);
+        let margin = quantile * stddev / (n as f64).sqrt();
+
+        Some(Summary {
+            samples: n,
+            mean,
+            median,
+            stddev,
+            min: sorted[0],
+            max: sorted[n - 1],
+            ci95_low: mean - margin,
+            ci95_high: mean + margin,
+        })
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "mean={:.6} median={:.6} stddev={:.6} min={:.6} max={:.6} ci95=[{:.6}, {:.6}] n={}",
+            self.mean,
+            self.median,
+            self.stddev,
+            self.min,
+            self.max,
+            self.ci95_low,
+            self.ci95_high,
+            self.samples
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn has_no_summary_without_samples() {
+        assert_eq!(Summary::from_samples(&[]), None);
+    }
+
+    #[test]
+    fn collapses_the_interval_of_a_single_sample() {
+        let summary = Summary::from_samples(&[1.5]).unwrap();
+        assert_eq!(summary.samples, 1);
+        assert_eq!(summary.mean, 1.5);
+        assert_eq!(summary.median, 1.5);
+        assert_eq!(summary.stddev, 0.0);
+        assert_eq!((summary.min, summary.max), (1.5, 1.5));
+        assert_eq!((summary.ci95_low, summary.ci95_high), (1.5, 1.5));
+    }
+
+    #[test]
+    fn uses_one_degree_of_freedom_for_two_samples() {
+        let summary = Summary::from_samples(&[3.0, 1.0]).unwrap();
+        assert_eq!(summary.mean, 2.0);
+        assert_eq!(summary.median, 2.0);
+        assert_close(summary.stddev, 2f64.sqrt());
+        // t = 12.706, and stddev / sqrt(2) = 1
+        assert_close(summary.ci95_low, 2.0 - 12.706);
+        assert_close(summary.ci95_high, 2.0 + 12.706);
+    }
+
+    #[test]
+    fn matches_a_hand_computed_interval() {
+        let summary = Summary::from_samples(&[9.0, 2.0, 4.0, 4.0, 5.0, 4.0, 7.0, 5.0]).unwrap();
+        assert_eq!(summary.samples, 8);
+        assert_eq!(summary.mean, 5.0);
+        assert_eq!(summary.median, 4.5);
+        assert_eq!((summary.min, summary.max), (2.0, 9.0));
+        // the squared deviations add up to 32, over 7 degrees of freedom
+        assert_close(summary.stddev, (32.0f64 / 7.0).sqrt());
+        // t = 2.365 for 7 degrees of freedom: 2.365 * 2.138090 / sqrt(8) = 1.787772
+        assert_close(summary.ci95_low, 3.212228);
+        assert_close(summary.ci95_high, 6.787772);
+    }
+
+    #[test]
+    fn uses_the_normal_quantile_above_30_degrees_of_freedom() {
+        let samples: Vec<f64> = (0..32).map(|i| (i % 2) as f64).collect();
+        let summary = Summary::from_samples(&samples).unwrap();
+        let margin = Z_QUANTILE * summary.stddev / 32f64.sqrt();
+        assert_close(summary.ci95_high - summary.mean, margin);
+    }
+}
@@ -0,0 +1,137 @@
+//! Tests of the energy meter against fake powercap trees.
+//!
+//! Each test writes a sysfs tree with RAPL zones under the Cargo temporary directory and changes
+//! the counters between the start and the end of a phase.
+
+use benchmarker::energy::EnergyMeter;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// A fresh sysfs root for `test`.
+fn sysfs(test: &str) -> PathBuf {
+    let root = Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join("sysfs")
+        .join(test);
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("class/powercap")).unwrap();
+    root
+}
+
+fn write_zone(root: &Path, zone: &str, name: &str, energy: u64, max_energy_range: u64) {
+    let dir = root.join("class/powercap").join(zone);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("name"), format!("{}\n", name)).unwrap();
+    fs::write(dir.join("energy_uj"), format!("{}\n", energy)).unwrap();
+    fs::write(
+        dir.join("max_energy_range_uj"),
+        format!("{}\n", max_energy_range),
+    )
+    .unwrap();
+}
+
+fn set_energy(root: &Path, zone: &str, energy: u64) {
+    let path = root.join("class/powercap").join(zone).join("energy_uj");
+    fs::write(path, format!("{}\n", energy)).unwrap();
+}
+
+#[test]
+fn sums_the_packages() {
+    let root = sysfs("sums_the_packages");
+    write_zone(
+        &root,
+        "intel-rapl:0",
+        "package-0",
+        1_000_000,
+        262_143_328_850,
+    );
+    write_zone(
+        &root,
+        "intel-rapl:1",
+        "package-1",
+        5_000_000,
+        262_143_328_850,
+    );
+    let meter = EnergyMeter::new(&root, 65);
+    assert!(!meter.is_estimate());
+
+    let start = meter.read();
+    set_energy(&root, "intel-rapl:0", 3_500_000);
+    set_energy(&root, "intel-rapl:1", 6_000_000);
+    let energy = meter.since(&start, Duration::from_secs(1));
+    assert!(!energy.estimated);
+    assert!((energy.joules - 3.5).abs() < 1e-9, "{:?}", energy);
+}
+
+#[test]
+fn leaves_out_subzones_and_psys() {
+    let root = sysfs("leaves_out_subzones_and_psys");
+    write_zone(&root, "intel-rapl:0", "package-0", 0, 1_000_000_000);
+    write_zone(&root, "intel-rapl:0:0", "core", 0, 1_000_000_000);
+    write_zone(&root, "intel-rapl:0:1", "dram", 0, 1_000_000_000);
+    write_zone(&root, "intel-rapl:1", "psys", 0, 1_000_000_000);
+    let meter = EnergyMeter::new(&root, 65);
+
+    let start = meter.read();
+    set_energy(&root, "intel-rapl:0", 2_000_000);
+    set_energy(&root, "intel-rapl:0:0", 1_500_000);
+    set_energy(&root, "intel-rapl:0:1", 300_000);
+    set_energy(&root, "intel-rapl:1", 9_000_000);
+    let energy = meter.since(&start, Duration::ZERO);
+    assert!((energy.joules - 2.0).abs() < 1e-9, "{:?}", energy);
+}
+
+#[test]
+fn corrects_a_wraparound() {
+    let root = sysfs("corrects_a_wraparound");
+    write_zone(&root, "intel-rapl:0", "package-0", 9_000_000, 10_000_000);
+    let meter = EnergyMeter::new(&root, 65);
+
+    let start = meter.read();
+    set_energy(&root, "intel-rapl:0", 500_000);
+    let energy = meter.since(&start, Duration::ZERO);
+    assert!(!energy.estimated);
+    // 1_000_000 µJ up to the maximum, 1 to wrap to 0 and 500_000 after
+    assert!((energy.joules - 1.500001).abs() < 1e-9, "{:?}", energy);
+}
+
+#[test]
+fn estimates_a_wraparound_from_above_the_range() {
+    let root = sysfs("estimates_a_wraparound_from_above_the_range");
+    write_zone(&root, "intel-rapl:0", "package-0", 12_000_000, 10_000_000);
+    let meter = EnergyMeter::new(&root, 64);
+
+    let start = meter.read();
+    set_energy(&root, "intel-rapl:0", 500_000);
+    let energy = meter.since(&start, Duration::from_secs(1));
+    assert!(energy.estimated);
+}
+
+#[test]
+fn estimates_without_rapl() {
+    let root = sysfs("estimates_without_rapl");
+    let meter = EnergyMeter::new(&root, 64);
+    assert!(meter.is_estimate());
+
+    let cores = std::thread::available_parallelism().unwrap().get() as f64;
+    let start = meter.read();
+    let energy = meter.since(&start, Duration::from_secs(2));
+    assert!(energy.estimated);
+    assert!(
+        (energy.joules - 2.0 * 64.0 / cores).abs() < 1e-9,
+        "{:?}",
+        energy
+    );
+}
+
+#[test]
+fn estimates_when_a_counter_disappears() {
+    let root = sysfs("estimates_when_a_counter_disappears");
+    write_zone(&root, "intel-rapl:0", "package-0", 0, 1_000_000_000);
+    let meter = EnergyMeter::new(&root, 64);
+
+    let start = meter.read();
+    fs::remove_file(root.join("class/powercap/intel-rapl:0/energy_uj")).unwrap();
+    let energy = meter.since(&start, Duration::from_secs(1));
+    assert!(energy.estimated);
+}
@@ -3,3 +3,6 @@ docker build -t zkvm-benchmarking-risc0 -f Dockerfile.risc0 .
 
 # built sp1 environment
 docker build -t zkvm-benchmarking-sp1 -f Dockerfile.sp1 .
+
+# built valida environment
+docker build --platform linux/amd64 -t zkvm-benchmarking-valida -f Dockerfile.valida .
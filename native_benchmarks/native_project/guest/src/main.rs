@@ -0,0 +1,6 @@
+native_runtime::entrypoint!(main);
+
+fn main() {
+    let n: u32 = native_runtime::read();
+    native_runtime::commit(&n);
+}
@@ -0,0 +1,220 @@
+//! In-process stand-in for the zkVM guest environment.
+//!
+//! The host pushes every generated input with [`write`], the guest pops them in the same order
+//! with [`read`] and records its public outputs with [`commit`]. The `_slice` variants pass raw
+//! bytes through unchanged. Values go through bincode so the guest sees exactly the types it
+//! would get from a zkVM (e.g. `&[u8]` written, `Vec<u8>` read), but nothing leaves the process.
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::Write;
+
+thread_local! {
+    static INPUTS: RefCell<VecDeque<Vec<u8>>> = RefCell::new(VecDeque::new());
+    static JOURNAL: RefCell<Vec<Vec<u8>>> = RefCell::new(Vec::new());
+}
+
+/// Expose the guest `main` as `guest::run()` so the host binary can call it in-process.
+#[macro_export]
+macro_rules! entrypoint {
+    ($path:path) => {
+        pub fn run() {
+            $path()
+        }
+    };
+}
+
+/// Queue an input value for the guest.
+pub fn write<T: Serialize + ?Sized>(value: &T) {
+    let bytes = bincode::serialize(value).expect("Failed to serialize input");
+    INPUTS.with(|inputs| inputs.borrow_mut().push_back(bytes));
+}
+
+/// Queue raw bytes for the guest, read back with [`read_slice`].
+pub fn write_slice(bytes: &[u8]) {
+    INPUTS.with(|inputs| inputs.borrow_mut().push_back(bytes.to_vec()));
+}
+
+/// Read the next input value queued by the host.
+pub fn read<T: DeserializeOwned>() -> T {
+    bincode::deserialize(&read_slice()).expect("Failed to deserialize input")
+}
+
+/// Read the next input queued by the host as raw bytes.
+pub fn read_slice() -> Vec<u8> {
+    INPUTS
+        .with(|inputs| inputs.borrow_mut().pop_front())
+        .expect("No more inputs were written by the host")
+}
+
+/// Record a public output of the guest.
+pub fn commit<T: Serialize + ?Sized>(value: &T) {
+    let bytes = bincode::serialize(value).expect("Failed to serialize output");
+    commit_slice(&bytes);
+}
+
+/// Record raw bytes as a public output of the guest.
+pub fn commit_slice(bytes: &[u8]) {
+    JOURNAL.with(|journal| journal.borrow_mut().push(bytes.to_vec()));
+}
+
+/// Write bytes to the standard output of the guest. The guest shares the process with the host,
+/// whose standard output is the benchmark log, so they go to the standard error instead and are
+/// never parsed as results.
+pub fn write_stdout(bytes: &[u8]) {
+    std::io::stderr()
+        .write_all(bytes)
+        .expect("Failed to write to stderr");
+}
+
+/// Write bytes to the standard error of the process.
+pub fn write_stderr(bytes: &[u8]) {
+    std::io::stderr()
+        .write_all(bytes)
+        .expect("Failed to write to stderr");
+}
+
+/// Take every value committed so far, one entry per `commit` call.
+pub fn take_journal() -> Vec<Vec<u8>> {
+    JOURNAL.with(|journal| std::mem::take(&mut *journal.borrow_mut()))
+}
+
+/// Decode one journal entry into the type the guest committed.
+pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> T {
+    bincode::deserialize(bytes).expect("Failed to deserialize output")
+}
+
+/// Counts the instructions retired by this process through `perf_event_open`.
+///
+/// Counting is unavailable outside Linux, on machines without a PMU (most VMs and containers
+/// without `--privileged`) or when `perf_event_paranoid` forbids it. In that case every method
+/// is a no-op and [`InstructionCounter::stop`] returns `None`.
+pub struct InstructionCounter {
+    fd: Option<i32>,
+}
+
+impl InstructionCounter {
+    pub fn new() -> InstructionCounter {
+        InstructionCounter { fd: perf::open_instruction_counter() }
+    }
+
+    pub fn start(&mut self) {
+        if let Some(fd) = self.fd {
+            perf::reset_and_enable(fd);
+        }
+    }
+
+    pub fn stop(&mut self) -> Option<u64> {
+        self.fd.and_then(perf::disable_and_read)
+    }
+}
+
+impl Default for InstructionCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for InstructionCounter {
+    fn drop(&mut self) {
+        if let Some(fd) = self.fd.take() {
+            perf::close(fd);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod perf {
+    const PERF_TYPE_HARDWARE: u32 = 0;
+    const PERF_COUNT_HW_INSTRUCTIONS: u64 = 1;
+    const PERF_EVENT_IOC_ENABLE: libc::c_ulong = 0x2400;
+    const PERF_EVENT_IOC_DISABLE: libc::c_ulong = 0x2401;
+    const PERF_EVENT_IOC_RESET: libc::c_ulong = 0x2403;
+
+    // disabled | inherit | exclude_kernel | exclude_hv
+    const FLAGS: u64 = (1 << 0) | (1 << 1) | (1 << 5) | (1 << 6);
+
+    /// The first published layout of `struct perf_event_attr` (PERF_ATTR_SIZE_VER0).
+    #[repr(C)]
+    #[derive(Default)]
+    struct PerfEventAttr {
+        kind: u32,
+        size: u32,
+        config: u64,
+        sample_period: u64,
+        sample_type: u64,
+        read_format: u64,
+        flags: u64,
+        wakeup_events: u32,
+        bp_type: u32,
+        config1: u64,
+    }
+
+    pub fn open_instruction_counter() -> Option<i32> {
+        let attr = PerfEventAttr {
+            kind: PERF_TYPE_HARDWARE,
+            size: std::mem::size_of::<PerfEventAttr>() as u32,
+            config: PERF_COUNT_HW_INSTRUCTIONS,
+            flags: FLAGS,
+            ..Default::default()
+        };
+        // measure this process on any CPU, without a group leader
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_perf_event_open,
+                &attr as *const PerfEventAttr,
+                0,
+                -1,
+                -1,
+                0,
+            )
+        };
+        if fd < 0 {
+            None
+        } else {
+            Some(fd as i32)
+        }
+    }
+
+    pub fn reset_and_enable(fd: i32) {
+        unsafe {
+            libc::ioctl(fd, PERF_EVENT_IOC_RESET as _, 0);
+            libc::ioctl(fd, PERF_EVENT_IOC_ENABLE as _, 0);
+        }
+    }
+
+    pub fn disable_and_read(fd: i32) -> Option<u64> {
+        let mut count: u64 = 0;
+        let read = unsafe {
+            libc::ioctl(fd, PERF_EVENT_IOC_DISABLE as _, 0);
+            libc::read(fd, &mut count as *mut u64 as *mut libc::c_void, 8)
+        };
+        if read == 8 {
+            Some(count)
+        } else {
+            None
+        }
+    }
+
+    pub fn close(fd: i32) {
+        unsafe {
+            libc::close(fd);
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod perf {
+    pub fn open_instruction_counter() -> Option<i32> {
+        None
+    }
+
+    pub fn reset_and_enable(_fd: i32) {}
+
+    pub fn disable_and_read(_fd: i32) -> Option<u64> {
+        None
+    }
+
+    pub fn close(_fd: i32) {}
+}
@@ -0,0 +1,2 @@
+#docker run --rm --platform linux/amd64 --privileged --ulimit nofile=65536:65536 -v "$(pwd):/app" zkvm-benchmarking-valida make valida TEST_NAME=fibonacci
+docker run --rm --platform linux/amd64 --privileged --ulimit nofile=65536:65536 -v "$(pwd):/app" zkvm-benchmarking-valida make valida TEST_NAME=isprime
@@ -0,0 +1,36 @@
+use benchmarker;
+use native_runtime::InstructionCounter;
+
+fn main() {
+    // INPUT_ASSIGNMENTS
+
+    let config = benchmarker::RunConfig::from_env();
+    config.print();
+    let bench = benchmarker::Benchmarker::with_config(config);
+
+    // Run the guest in-process, without any zkVM in between.
+    let ((instructions, journal), measurements) = bench.repeat("execute", || {
+        // ENVIRONMENT_BUILDER
+
+        let mut counter = InstructionCounter::new();
+        counter.start();
+        guest::run();
+        let instructions = counter.stop();
+        (instructions, native_runtime::take_journal())
+    });
+    measurements.report("Execution");
+    bench.report_phases();
+
+    match instructions {
+        Some(count) => println!("Instructions retired: {}", count),
+        None => println!("Instructions retired: unavailable"),
+    }
+
+    for output in &journal {
+        println!("Committed output: {}", hex::encode(output));
+    }
+
+    // OUTPUT_DECODING
+
+    // OUTPUT_CHECK
+}
@@ -1,7 +1,8 @@
 use methods::{
     TEST_PROJECT_ELF, TEST_PROJECT_ID
 };
-use risc0_zkvm::{default_prover, ExecutorEnv};
+use risc0_zkvm::{default_prover, ExecutorEnv, ExecutorImpl, ProverOpts};
+use benchmarker::ProofKind;
 use benchmarker;
 
 fn main() {
@@ -12,39 +13,70 @@ fn main() {
 
     // INPUT_ASSIGNMENTS
 
-    let env = // ENVIRONMENT_BUILDER;
+    let config = benchmarker::RunConfig::from_env();
+    config.print();
+    let bench = benchmarker::Benchmarker::with_config(config);
 
-    let prover = default_prover();
-    
-    let mut benchmarker = benchmarker::Benchmarker::new();
-    benchmarker.start_benchmark();
-    let prove_info = prover
-        .prove(env, TEST_PROJECT_ELF)
-        .unwrap();
-    let benchmark_results = benchmarker.end_benchmark();
-    //log proving benchmark results
-    if let Some(duration) = benchmark_results {
-        println!("Proving time: {:?}", duration);
+    if benchmarker::Mode::from_env() == benchmarker::Mode::Execute {
+        // Run the executor only, to get the cycle counts without proving.
+        let (session, execution_measurements) = bench.repeat("execute", || {
+            let env = // ENVIRONMENT_BUILDER;
+            ExecutorImpl::from_elf(env, TEST_PROJECT_ELF)
+                .unwrap()
+                .run()
+                .unwrap()
+        });
+        execution_measurements.report("Execution");
+        println!("Total cycles: {}", session.total_cycles);
+        println!("User cycles: {}", session.user_cycles);
+        println!("Paging cycles: {}", session.paging_cycles);
+        println!("Segments: {}", session.segments.len());
+        bench.report_phases();
+        return;
     }
 
-    let receipt = prove_info.receipt;
+    let proof_kind = ProofKind::from_env().unwrap_or_else(|err| panic!("{}", err));
+    let opts = match proof_kind {
+        ProofKind::Composite => ProverOpts::composite(),
+        ProofKind::Succinct => ProverOpts::succinct(),
+        ProofKind::Groth16 => ProverOpts::groth16(),
+        unsupported => unsupported.unsupported("RISC Zero"),
+    };
+    proof_kind.print();
+
+    let prover = bench.phase("setup", default_prover);
 
-    let serialized_receipt = bincode::serialize(&receipt).unwrap();
+    // Prove the segments, then compress them into the requested kind of proof.
+    let (receipt, proving_measurements) = bench.repeat("prove", || {
+        let prove_info = bench.phase("composite", || {
+            let env = // ENVIRONMENT_BUILDER;
+            prover
+                .prove_with_opts(env, TEST_PROJECT_ELF, &ProverOpts::composite())
+                .unwrap()
+        });
+        if proof_kind == ProofKind::Composite {
+            return prove_info.receipt;
+        }
+        bench.phase("compress", || prover.compress(&opts, &prove_info.receipt).unwrap())
+    });
+    //log proving benchmark results
+    proving_measurements.report("Proving");
+
+    let serialized_receipt = bench.phase("serialize", || bincode::serialize(&receipt).unwrap());
     let size_in_kb = serialized_receipt.len() as f64 / 1024.0;
     println!("Proof size: {} KB", size_in_kb);
 
-    let output: u32 = receipt.journal.decode().unwrap();
-    // println!("Output: {}", output);
+    // OUTPUT_DECODING
 
-    let mut verifying_benchmarker = benchmarker::Benchmarker::new();
-    verifying_benchmarker.start_benchmark();
-    receipt
-        .verify(TEST_PROJECT_ID)
-        .unwrap();
-    let verifying_benchmark_results = verifying_benchmarker.end_benchmark();
+    let (_, verifying_measurements) = bench.repeat("verify", || {
+        receipt
+            .verify(TEST_PROJECT_ID)
+            .unwrap()
+    });
 
     //logs verification benchmark results
-    if let Some(duration) = verifying_benchmark_results {
-        println!("Verification time: {:?}", duration);
-    } 
+    verifying_measurements.report("Verification");
+    bench.report_phases();
+
+    // OUTPUT_CHECK
 }
\ No newline at end of file
@@ -1,5 +1,6 @@
 use sp1_sdk::{include_elf, utils, ProverClient, SP1ProofWithPublicValues, SP1Stdin};
 use benchmarker;
+use benchmarker::ProofKind;
 
 /// The ELF we want to execute inside the zkVM.
 const ELF: &[u8] = include_elf!("fibonacci-program");
@@ -12,40 +13,64 @@ fn main() {
 
     // ENVIRONMENT_BUILDER
 
+    let config = benchmarker::RunConfig::from_env();
+    config.print();
+    let bench = benchmarker::Benchmarker::with_config(config);
+
     // Create a `ProverClient` method.
     let client = ProverClient::new();
 
     // Execute the program using the `ProverClient.execute` method, without generating a proof.
-    // let (_, report) = client.execute(ELF, stdin.clone()).run().unwrap();
-    // println!("executed program with {} cycles", report.total_instruction_count());
+    if benchmarker::Mode::from_env() == benchmarker::Mode::Execute {
+        let ((_, report), execution_measurements) = bench.repeat("execute", || {
+            client.execute(ELF, stdin.clone()).run().unwrap()
+        });
+        execution_measurements.report("Execution");
+        println!("Total cycles: {}", report.total_instruction_count());
+        println!("Syscalls: {}", report.total_syscall_count());
+        bench.report_phases();
+        return;
+    }
 
-    // Generate the proof for the given program and input.
-    let (pk, vk) = client.setup(ELF);
-    let mut benchmarker = benchmarker::Benchmarker::new();
-    benchmarker.start_benchmark();
-    let mut proof = client.prove(&pk, stdin).run().unwrap();
-    let benchmark_results = benchmarker.end_benchmark();
-    if let Some(duration) = benchmark_results {
-        println!("Proving time: {:?}", duration);
+    let proof_kind = ProofKind::from_env().unwrap_or_else(|err| panic!("{}", err));
+    if let ProofKind::Succinct = proof_kind {
+        proof_kind.unsupported("SP1");
     }
+    proof_kind.print();
+
+    // Generate the proving and verifying keys.
+    let ((pk, vk), setup_measurements) = bench.repeat("setup", || client.setup(ELF));
+    setup_measurements.report("Setup");
+
+    // Generate the proof for the given program and input.
+    let (mut proof, proving_measurements) = bench.repeat("prove", || {
+        let builder = client.prove(&pk, stdin.clone());
+        let builder = match proof_kind {
+            ProofKind::Compressed => builder.compressed(),
+            ProofKind::Groth16 => builder.groth16(),
+            ProofKind::Plonk => builder.plonk(),
+            _ => builder.core(),
+        };
+        builder.run().unwrap()
+    });
+    proving_measurements.report("Proving");
     //serialize the receipt to its bytes and log its size in kb
-    let serialized_receipt = bincode::serialize(&proof).unwrap();
+    let serialized_receipt = bench.phase("serialize", || bincode::serialize(&proof).unwrap());
     let size_in_kb = serialized_receipt.len() as f64 / 1024.0;
     println!("Proof size: {} KB", size_in_kb);
 
 
     // Read and verify the output.
-    let a = proof.public_values.read::<u32>();
-    // println!("Output: {}", a);
+    // OUTPUT_DECODING
 
     // Verify proof and public values
-    let mut verifying_benchmarker = benchmarker::Benchmarker::new();
-    verifying_benchmarker.start_benchmark();
-    client.verify(&proof, &vk).expect("verification failed");
-    let verifying_benchmark_results = verifying_benchmarker.end_benchmark();
+    let (_, verifying_measurements) = bench.repeat("verify", || {
+        client.verify(&proof, &vk).expect("verification failed")
+    });
 
     //logs verification benchmark results
-    if let Some(duration) = verifying_benchmark_results {
-        println!("Verification time: {:?}", duration);
-    }
+    verifying_measurements.report("Verification");
+    bench.report_phases();
+
+    // OUTPUT_CHECK
 }
\ No newline at end of file
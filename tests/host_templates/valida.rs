@@ -0,0 +1,57 @@
+use std::fs;
+use std::process::{Command, Stdio};
+use benchmarker;
+
+/// The Valida program built by `cargo +valida build --release` in `../program`.
+const PROGRAM: &str = "../program/target/valida-unknown-baremetal-gnu/release/program";
+const STDIN_PATH: &str = "../target/valida_stdin.txt";
+const OUTPUT_PATH: &str = "../target/valida_output.txt";
+const PROOF_PATH: &str = "../target/valida_proof";
+
+/// Run a `valida` subcommand with the generated inputs piped into its stdin.
+fn valida(args: &[&str]) {
+    let status = Command::new("valida")
+        .args(args)
+        .stdin(Stdio::from(fs::File::open(STDIN_PATH).unwrap()))
+        .status()
+        .expect("Failed to run valida");
+    assert!(status.success(), "valida {} failed", args.join(" "));
+}
+
+fn main() {
+    // INPUT_ASSIGNMENTS
+
+    // ENVIRONMENT_BUILDER
+
+    let config = benchmarker::RunConfig::from_env();
+    config.print();
+    let bench = benchmarker::Benchmarker::with_config(config);
+
+    fs::create_dir_all("../target").unwrap();
+    fs::write(STDIN_PATH, stdin).unwrap();
+
+    // Execute the program once to capture the committed outputs.
+    bench.phase("execute", || valida(&["run", PROGRAM, OUTPUT_PATH]));
+
+    let (_, proving_measurements) = bench.repeat("prove", || {
+        valida(&["prove", PROGRAM, PROOF_PATH])
+    });
+    proving_measurements.report("Proving");
+
+    let size_in_kb = fs::metadata(PROOF_PATH).unwrap().len() as f64 / 1024.0;
+    println!("Proof size: {} KB", size_in_kb);
+
+    let output = fs::read_to_string(OUTPUT_PATH).unwrap();
+    let mut output_lines = output.lines();
+    // OUTPUT_DECODING
+
+    let (_, verifying_measurements) = bench.repeat("verify", || {
+        valida(&["verify", PROGRAM, PROOF_PATH])
+    });
+
+    //logs verification benchmark results
+    verifying_measurements.report("Verification");
+    bench.report_phases();
+
+    // OUTPUT_CHECK
+}
\ No newline at end of file
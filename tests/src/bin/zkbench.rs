@@ -0,0 +1,117 @@
+use benchmarker::{Mode, ProofKind, RunConfig};
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+use tests::config::Config;
+use tests::runner::{self, RunError, RunOptions, Vm};
+
+/// Generate, build, run and measure the zkVM benchmarks.
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    /// The zkbench.toml that locates the zkVM projects (defaults to the closest one)
+    #[clap(long, global = true)]
+    config: Option<PathBuf>,
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Benchmark one test template on one zkVM
+    Run {
+        /// risc0, sp1, valida or native
+        #[clap(long)]
+        vm: Vm,
+        /// Name of a directory in the templates directory
+        #[clap(long)]
+        test: String,
+        /// Defaults to `templates` of the config
+        #[clap(long)]
+        templates_dir: Option<PathBuf>,
+        /// Defaults to `results` of the config
+        #[clap(long)]
+        results_dir: Option<PathBuf>,
+        /// Where the project copy of each zkVM and test is generated and built, defaults to
+        /// `build` of the config
+        #[clap(long)]
+        build_dir: Option<PathBuf>,
+        /// Unmeasured runs of each phase before measuring
+        #[clap(long, default_value_t = 0)]
+        warmup: usize,
+        /// Measured runs of each phase
+        #[clap(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+        runs: u64,
+        /// How often the memory of the processes the host starts is sampled, in milliseconds
+        #[clap(long, default_value_t = 10, value_parser = clap::value_parser!(u64).range(1..))]
+        memory_sample_ms: u64,
+        /// TDP of the CPU in watts, used to estimate the energy where RAPL is unavailable
+        #[clap(long, default_value_t = benchmarker::energy::DEFAULT_TDP_WATTS)]
+        tdp_watts: u32,
+        /// Only execute the program and report cycle counts, without proving (risc0, sp1)
+        #[clap(long)]
+        execute: bool,
+        /// composite, succinct, compressed, groth16 or plonk (defaults to the zkVM default proof)
+        #[clap(long)]
+        proof_kind: Option<ProofKind>,
+    },
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let config = match &cli.config {
+        Some(path) => Config::load(path),
+        None => Config::find(),
+    };
+    let config = config.unwrap_or_else(|err| {
+        eprint!("{}", err);
+        process::exit(1);
+    });
+
+    match cli.command {
+        Commands::Run {
+            vm,
+            test,
+            templates_dir,
+            results_dir,
+            build_dir,
+            warmup,
+            runs,
+            memory_sample_ms,
+            tdp_watts,
+            execute,
+            proof_kind,
+        } => {
+            let options = RunOptions {
+                vm,
+                test_name: test,
+                templates_dir: templates_dir.unwrap_or_else(|| config.templates.clone()),
+                results_dir: results_dir.unwrap_or_else(|| config.results.clone()),
+                build_dir: build_dir.unwrap_or_else(|| config.build.clone()),
+                config,
+                runs: RunConfig {
+                    warmup,
+                    runs: runs as usize,
+                    sample_interval: Duration::from_millis(memory_sample_ms),
+                    tdp_watts,
+                    ..RunConfig::default()
+                },
+                mode: if execute { Mode::Execute } else { Mode::Prove },
+                proof_kind,
+            };
+            runner::stop_on_interrupt();
+            match runner::run(&options) {
+                Ok(_) => {}
+                Err(RunError::Interrupted) => {
+                    eprintln!("Interrupted, the running builds were stopped");
+                    process::exit(130);
+                }
+                Err(err) => {
+                    eprintln!("Error: {}", err);
+                    process::exit(1);
+                }
+            }
+        }
+    }
+}
@@ -1,270 +1,300 @@
+use crate::diagnostics::CodegenError;
 use crate::env_adapters::CodeEnv;
-use quote::{quote, ToTokens};
-use std::{collections::HashMap, fs, path::Path};
-use syn::{parse_file, spanned::Spanned, Attribute, Expr, File, Item, ItemFn, Stmt};
-use toml;
+use crate::guest_io::{GuestIo, GuestRewriter};
+use crate::manifest::{rebase_dependency, toml_error, Manifest};
+use crate::render::{render, LineMap};
+use proc_macro2::Span;
+use quote::ToTokens;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+use syn::parse::Parser;
+use syn::spanned::Spanned;
+use syn::visit_mut::VisitMut;
+use syn::{parse_file, Attribute, File, Item, ItemFn, ReturnType, Type};
+use toml_edit::ImDocument;
 
 pub struct CodeGenerator {
     env: Box<dyn CodeEnv>,
-    saved_state: SavedState,
-}
-
-struct SavedState {
-    host_output_dir: String,
-    guest_output_dir: String,
-    host_cargo_toml: String,
-    guest_cargo_toml: String,
-    host_files: HashMap<String, Vec<u8>>,
-    guest_files: HashMap<String, Vec<u8>>,
+    /// The checked-in project the project of `env` was copied from
+    template_project: PathBuf,
+    line_maps: Vec<LineMap>,
 }
 
 impl CodeGenerator {
-    pub fn new(env: Box<dyn CodeEnv>) -> Self {
-        let saved_state = Self::save_initial_state(&env);
-        Self { env, saved_state }
-    }
-
-    fn save_initial_state(env: &Box<dyn CodeEnv>) -> SavedState {
-        let host_output_dir = env.get_host_output_dir();
-        let guest_output_dir = env.get_guest_output_dir();
-        let host_cargo_toml_path = env.get_host_cargo_toml_path();
-        let guest_cargo_toml_path = env.get_guest_cargo_toml_path();
-
-        let host_cargo_toml =
-            fs::read_to_string(&host_cargo_toml_path).expect("Failed to read host Cargo.toml");
-        let guest_cargo_toml =
-            fs::read_to_string(&guest_cargo_toml_path).expect("Failed to read guest Cargo.toml");
-
-        let host_files = Self::read_directory_files(&host_output_dir);
-        let guest_files = Self::read_directory_files(&guest_output_dir);
-
-        SavedState {
-            host_output_dir,
-            guest_output_dir,
-            host_cargo_toml,
-            guest_cargo_toml,
-            host_files,
-            guest_files,
-        }
-    }
-
-    fn read_directory_files(dir: &str) -> HashMap<String, Vec<u8>> {
-        let mut files = HashMap::new();
-        for entry in fs::read_dir(dir).expect("Failed to read directory") {
-            let entry = entry.expect("Failed to read directory entry");
-            let path = entry.path();
-            if path.is_file() {
-                let file_name = path.file_name().unwrap().to_str().unwrap().to_string();
-                let content = fs::read(&path).expect("Failed to read file");
-                files.insert(file_name, content);
+    /// Generate into a fresh copy of `template_project`, made at the project directory of `env`.
+    /// The copy replaces the sources of a previous one but keeps its `target` directory, so
+    /// builds stay incremental. `template_project` itself is never changed.
+    pub fn scaffold(env: Box<dyn CodeEnv>, template_project: &Path) -> Result<Self, CodegenError> {
+        let workspace = PathBuf::from(env.get_project_dir());
+        copy_project(template_project, &workspace)?;
+
+        let generator = Self {
+            env,
+            template_project: template_project.to_path_buf(),
+            line_maps: Vec::new(),
+        };
+        for manifest_path in find_manifests(&workspace)? {
+            let mut manifest = Manifest::load(&manifest_path)?;
+            if let Some(original_dir) = generator.original_dir(&manifest_path) {
+                manifest.rebase_paths(&original_dir, template_project);
             }
+            manifest.save()?;
         }
-        files
+        Ok(generator)
     }
 
-    pub fn reset(&self) {
-        fs::remove_dir_all(&self.saved_state.host_output_dir)
-            .expect("Failed to remove host output directory");
-        fs::create_dir_all(&self.saved_state.host_output_dir)
-            .expect("Failed to create host output directory");
-
-        fs::remove_dir_all(&self.saved_state.guest_output_dir)
-            .expect("Failed to remove guest output directory");
-        fs::create_dir_all(&self.saved_state.guest_output_dir)
-            .expect("Failed to create guest output directory");
-
-        for (file_name, content) in &self.saved_state.host_files {
-            let file_path = Path::new(&self.saved_state.host_output_dir).join(file_name);
-            fs::write(file_path, content).expect("Failed to write host file");
-        }
-
-        for (file_name, content) in &self.saved_state.guest_files {
-            let file_path = Path::new(&self.saved_state.guest_output_dir).join(file_name);
-            fs::write(file_path, content).expect("Failed to write guest file");
-        }
-
-        let host_cargo_toml_path = self.env.get_host_cargo_toml_path();
-        fs::write(host_cargo_toml_path, &self.saved_state.host_cargo_toml)
-            .expect("Failed to write host Cargo.toml");
-
-        let guest_cargo_toml_path = self.env.get_guest_cargo_toml_path();
-        fs::write(guest_cargo_toml_path, &self.saved_state.guest_cargo_toml)
-            .expect("Failed to write guest Cargo.toml");
+    /// Where the lines of the generated guest and host programs come from in the template.
+    pub fn line_maps(&self) -> &[LineMap] {
+        &self.line_maps
     }
 
-    pub fn generate_code(&self, input_dir: &str, output_dir: &str, host_output_dir: &str) {
-        let dir_name = Path::new(input_dir)
+    pub fn generate_code(
+        &mut self,
+        input_dir: &str,
+        output_dir: &str,
+        host_output_dir: &str,
+    ) -> Result<(), CodegenError> {
+        let input_dir = Path::new(input_dir);
+        let dir_name = input_dir
             .file_name()
-            .expect("Failed to get directory name")
-            .to_str()
-            .expect("Failed to convert directory name to string");
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| CodegenError::new("invalid template directory").in_file(input_dir))?;
 
-        let mut template_path = String::new();
-        let mut toml_path = String::new();
+        let mut template_path = None;
+        let mut toml_path = None;
         let mut additional_file_paths = Vec::new();
 
-        for entry in fs::read_dir(input_dir).expect("Failed to read input directory") {
-            let entry = entry.expect("Failed to read directory entry");
+        let entries = fs::read_dir(input_dir)
+            .map_err(|err| CodegenError::io("read the template directory", input_dir, err))?;
+        for entry in entries {
+            let entry = entry
+                .map_err(|err| CodegenError::io("read the template directory", input_dir, err))?;
             let path = entry.path();
             if path.is_file() {
                 let file_name = path.file_name().unwrap().to_str().unwrap();
                 if file_name == format!("{}.rs", dir_name) {
-                    template_path = path.to_str().unwrap().to_string();
+                    template_path = Some(path);
                 } else if file_name.ends_with(".toml") {
-                    toml_path = path.to_str().unwrap().to_string();
+                    toml_path = Some(path);
                 } else {
-                    additional_file_paths.push(path.to_str().unwrap().to_string());
+                    additional_file_paths.push(path);
                 }
             }
         }
 
-        let host_output_path = Path::new(host_output_dir)
-            .join("main.rs")
-            .to_str()
-            .unwrap()
-            .to_string();
-        self.generate_host_code(&template_path, &host_output_path);
-        prepend_host_imports_to_syn_tree(&template_path, &host_output_path);
-        append_host_functions_to_syn_tree(&template_path, &host_output_path);
-        self.handle_precompiles(&template_path);
-        self.handle_dependencies(&toml_path);
-
-        let code = fs::read_to_string(&template_path).expect("Failed to read input file");
-        let mut syntax_tree: File = parse_file(&code).expect("Failed to parse Rust code");
-
+        let template_path = template_path.ok_or_else(|| {
+            CodegenError::new(format!("no `{}.rs` in the template directory", dir_name))
+                .in_file(input_dir)
+                .with_help("a template directory holds a Rust file named after the directory")
+        })?;
+        let toml_path = toml_path.ok_or_else(|| {
+            CodegenError::new("no dependency file in the template directory")
+                .in_file(input_dir)
+                .with_help(format!(
+                    "add a `{}.toml` with `[host_dependencies]` and `[guest_dependencies]` tables",
+                    dir_name
+                ))
+        })?;
+        let template = Template::load(&template_path)?;
+
+        let host_output_path = Path::new(host_output_dir).join("main.rs");
+        self.generate_host_code(&template, &host_output_path)?;
+        prepend_host_imports_to_syn_tree(&template, &host_output_path)?;
+        let host_line_map = append_host_functions_to_syn_tree(&template, &host_output_path)?;
+        self.handle_precompiles(&template)?;
+        self.handle_dependencies(&toml_path)?;
+
+        let mut syntax_tree = template.syntax_tree.clone();
         strip_attribute(&mut syntax_tree, "precompile");
         let transformed = self.transform(syntax_tree);
 
         let output_path = Path::new(output_dir).join("main.rs");
-        fs::write(output_path, quote!(#transformed).to_string())
-            .expect("Failed to write output file");
+        let (guest_code, guest_line_map) = render(&transformed, &template, &output_path);
+        write_atomically(&output_path, guest_code)?;
+        self.line_maps = vec![guest_line_map, host_line_map];
 
         for path in additional_file_paths {
-            self.copy_additional_files(&path);
+            self.copy_additional_files(&path)?;
         }
+        Ok(())
     }
 
-    fn copy_additional_files(&self, input_path: &str) {
-        let template_dir = Path::new(input_path).parent().unwrap();
+    fn copy_additional_files(&mut self, input_path: &Path) -> Result<(), CodegenError> {
+        let template_dir = input_path.parent().unwrap();
         let binding = self.env.get_file_copy_destination();
         let destination_dir = Path::new(&binding);
         println!("Path: {}", destination_dir.display());
 
-        for entry in fs::read_dir(template_dir).expect("Failed to read template directory") {
-            let entry = entry.expect("Failed to read directory entry");
+        let entries = fs::read_dir(template_dir)
+            .map_err(|err| CodegenError::io("read the template directory", template_dir, err))?;
+        for entry in entries {
+            let entry = entry.map_err(|err| {
+                CodegenError::io("read the template directory", template_dir, err)
+            })?;
             let path = entry.path();
             if path.is_file() {
                 let file_name = path.file_name().unwrap();
                 let destination_path = destination_dir.join(file_name);
-                fs::copy(&path, &destination_path).expect("Failed to copy file");
+                let content =
+                    fs::read(&path).map_err(|err| CodegenError::io("copy the file", &path, err))?;
+                write_atomically(&destination_path, content)?;
             }
         }
+        Ok(())
     }
 
-    fn handle_dependencies(&self, toml_path: &str) {
-        let toml_content = fs::read_to_string(toml_path).expect("Failed to read rsa.toml");
-        let toml_value: toml::Value =
-            toml::from_str(&toml_content).expect("Failed to parse rsa.toml");
-
-        let host_dependencies = toml_value
-            .get("host_dependencies")
-            .expect("Missing host_dependencies");
-        let guest_dependencies = toml_value
-            .get("guest_dependencies")
-            .expect("Missing guest_dependencies");
-
-        let host_cargo_toml_path = self.env.get_host_cargo_toml_path();
-        let guest_cargo_toml_path = self.env.get_guest_cargo_toml_path();
-
-        self.update_cargo_toml(&host_cargo_toml_path, host_dependencies);
-        self.update_cargo_toml(&guest_cargo_toml_path, guest_dependencies);
-    }
-
-    fn handle_precompiles(&self, input_path: &str) {
-        let code = fs::read_to_string(input_path).expect("Failed to read input file");
-        let syntax_tree: File = parse_file(&code).expect("Failed to parse Rust code");
-
-        // Process items with #[precompile] attribute
-        let precompile_items = process_items_with_attribute(
-            syntax_tree.items,
-            "precompile",
-            true,  // strip_attribute
-            false, // strip_item
-        );
-
-        let mut patches = HashMap::new();
-
-        for item in precompile_items {
-            if let syn::Item::Use(use_item) = item {
-                if let syn::UseTree::Path(path) = &use_item.tree {
-                    let crate_name = path.ident.to_string();
-                    if let Some(patch) = self.env.get_available_patches().get(&crate_name) {
-                        patches.insert(crate_name, patch.clone());
-                    }
+    /// Add the `[host_dependencies]` and `[guest_dependencies]` of the template to the project
+    /// manifests. A template dependency replaces the project's dependency of the same name; if
+    /// the two ask for different versions of the crate, a warning points at the template's line.
+    fn handle_dependencies(&mut self, toml_path: &Path) -> Result<(), CodegenError> {
+        let source = fs::read_to_string(toml_path)
+            .map_err(|err| CodegenError::io("read the dependency file", toml_path, err))?;
+        let dependency_file = ImDocument::parse(source.as_str())
+            .map_err(|err| toml_error(toml_path, &source, err))?;
+
+        let sections = [
+            ("host_dependencies", self.env.get_host_cargo_toml_path()),
+            ("guest_dependencies", self.env.get_guest_cargo_toml_path()),
+        ];
+        // The spans only exist in the parsed document, the items are taken from an owned copy
+        let mut spans = HashMap::new();
+        for (section, _) in &sections {
+            let dependencies = dependency_file
+                .get(section)
+                .and_then(toml_edit::Item::as_table_like)
+                .ok_or_else(|| {
+                    CodegenError::new(format!("missing `[{}]` table", section))
+                        .in_file(toml_path)
+                        .with_help(format!("add `[{}]`, it may be empty", section))
+                })?;
+            for (name, item) in dependencies.iter() {
+                let key = dependencies.key(name).and_then(|key| key.span());
+                if let (Some(key), Some(item)) = (key, item.span()) {
+                    spans.insert((*section, name.to_string()), key.start..item.end);
                 }
             }
         }
-
-        if !patches.is_empty() {
-            let cargo_toml_path = self.env.get_workspace_cargo_toml_path();
-            let mut cargo_toml_content = fs::read_to_string(&cargo_toml_path).expect("Failed to read Cargo.toml");
-            let mut cargo_toml_value: toml::Value = toml::from_str(&cargo_toml_content).expect("Failed to parse Cargo.toml");
-
-            // Ensure the patch section exists
-            let patch_section = cargo_toml_value.as_table_mut().unwrap().entry("patch").or_insert_with(|| {
-                toml::Value::Table(toml::map::Map::new())
-            });
-
-            // Ensure the crates-io section exists within the patch section
-            let crates_io_section = patch_section.as_table_mut().unwrap().entry("crates-io").or_insert_with(|| {
-                toml::Value::Table(toml::map::Map::new())
-            });
-
-            if let toml::Value::Table(crates_io_table) = crates_io_section {
-                for (crate_name, patch) in patches {
-                    let patch_value: toml::Value = toml::from_str(&patch).expect("Failed to parse patch");
-                    if let toml::Value::Table(patch_table) = patch_value {
-                        for (key, value) in patch_table {
-                            crates_io_table.insert(key, value);
-                        }
-                    }
+        let dependency_file = dependency_file.into_mut();
+
+        for (section, cargo_toml_path) in &sections {
+            let mut manifest = Manifest::load(Path::new(cargo_toml_path))?;
+            let original_dir = self.original_dir(manifest.path());
+            let dependencies = dependency_file[section]
+                .as_table_like()
+                .expect("checked above");
+            for (name, dependency) in dependencies.iter() {
+                // path dependencies are written relative to the checked-in project
+                let mut dependency = dependency.clone();
+                if let Some(original_dir) = &original_dir {
+                    rebase_dependency(&mut dependency, original_dir, &self.template_project);
                 }
+                let Some(existing) = manifest.add_dependency(name, &dependency)? else {
+                    continue;
+                };
+                let message = format!(
+                    "`{}` replaces `{}` of {}",
+                    name,
+                    existing,
+                    manifest.path().display()
+                );
+                let warning = match spans.get(&(*section, name.to_string())) {
+                    Some(span) => {
+                        CodegenError::at_offset(toml_path, &source, span.clone(), message)
+                    }
+                    None => CodegenError::new(message).in_file(toml_path),
+                };
+                eprint!(
+                    "{}",
+                    warning.as_warning().with_help(
+                        "the project asks for another version or source of the crate, \
+                         the template's dependency is used"
+                    )
+                );
             }
-
-            println!("Patched Cargo.toml: {:#?}", cargo_toml_value);
-
-            cargo_toml_content = toml::to_string(&cargo_toml_value).expect("Failed to serialize Cargo.toml");
-            fs::write(cargo_toml_path, cargo_toml_content).expect("Failed to write Cargo.toml");
+            manifest.save()?;
         }
+        Ok(())
+    }
+
+    /// The directory of the checked-in project a file of the copy comes from. Relative `path`
+    /// dependencies are resolved from there.
+    fn original_dir(&self, path: &Path) -> Option<PathBuf> {
+        let relative = path.strip_prefix(self.env.get_project_dir()).ok()?;
+        let original = self.template_project.join(relative);
+        original.parent().map(Path::to_path_buf)
     }
 
+    fn handle_precompiles(&mut self, template: &Template) -> Result<(), CodegenError> {
+        let precompile_items = template.syntax_tree.items.iter().filter(|item| match item {
+            Item::Use(use_item) => use_item
+                .attrs
+                .iter()
+                .any(|attr| attr.path().is_ident("precompile")),
+            _ => false,
+        });
 
-    fn update_cargo_toml(&self, cargo_toml_path: &str, dependencies: &toml::Value) {
-        let mut cargo_toml_content =
-            fs::read_to_string(cargo_toml_path).expect("Failed to read Cargo.toml");
-        let mut cargo_toml_value: toml::Value =
-            toml::from_str(&cargo_toml_content).expect("Failed to parse Cargo.toml");
+        let available_patches = self.env.get_available_patches();
+        // Sorted, so the patches are added in the same order on every run
+        let mut patches = BTreeMap::new();
 
-        let deps = cargo_toml_value
-            .get_mut("dependencies")
-            .expect("Missing dependencies section");
-        if let toml::Value::Table(deps_table) = deps {
-            if let toml::Value::Table(new_deps) = dependencies {
-                for (key, value) in new_deps {
-                    deps_table.insert(key.clone(), value.clone());
+        for item in precompile_items {
+            let Item::Use(use_item) = item else {
+                continue;
+            };
+            let syn::UseTree::Path(path) = &use_item.tree else {
+                return Err(template
+                    .error(use_item.tree.span(), "expected a crate path")
+                    .with_help("write precompiled imports as `use <crate>::...;`"));
+            };
+            let crate_name = path.ident.to_string();
+            match available_patches.get(&crate_name) {
+                Some(patch) => {
+                    patches.insert(crate_name, patch.clone());
+                }
+                // zkVMs without any patches run every crate unaccelerated
+                None if available_patches.is_empty() => {}
+                None => {
+                    let mut available: Vec<String> = available_patches
+                        .keys()
+                        .map(|name| format!("`{}`", name))
+                        .collect();
+                    available.sort();
+                    return Err(template
+                        .error(
+                            path.ident.span(),
+                            format!("no precompile is available for `{}`", crate_name),
+                        )
+                        .with_help(format!("available precompiles: {}", available.join(", "))));
                 }
             }
         }
 
-        cargo_toml_content =
-            toml::to_string(&cargo_toml_value).expect("Failed to serialize Cargo.toml");
-        fs::write(cargo_toml_path, cargo_toml_content).expect("Failed to write Cargo.toml");
+        if !patches.is_empty() {
+            let cargo_toml_path = self.env.get_workspace_cargo_toml_path();
+            let mut manifest = Manifest::load(Path::new(&cargo_toml_path))?;
+            for patch in patches.into_values() {
+                manifest.add_patch(&patch)?;
+            }
+            manifest.save()?;
+        }
+        Ok(())
     }
 
     fn transform(&self, mut syntax_tree: syn::File) -> syn::File {
         let mut new_items = Vec::new();
 
         for import in self.env.import() {
+            // inner attributes like `#![no_main]` belong to the file rather than its items
+            if let syn::Item::Verbatim(tokens) = &import {
+                if let Ok(attrs) = Attribute::parse_inner.parse2(tokens.clone()) {
+                    syntax_tree.attrs.extend(attrs);
+                    continue;
+                }
+            }
             new_items.push(import);
         }
 
@@ -308,11 +338,6 @@ impl CodeGenerator {
 
                     new_items.push(item);
                 }
-                syn::Item::Fn(func) if func.sig.ident == "main" => {
-                    let mut transformed_func = func.clone();
-                    self.transform_main_body(&mut transformed_func);
-                    new_items.push(syn::Item::Fn(transformed_func));
-                }
                 _ => {
                     new_items.push(item);
                 }
@@ -320,124 +345,209 @@ impl CodeGenerator {
         }
 
         syntax_tree.items = new_items;
+        GuestRewriter {
+            env: self.env.as_ref(),
+        }
+        .visit_file_mut(&mut syntax_tree);
         syntax_tree
     }
 
-    fn transform_main_body(&self, func: &mut syn::ItemFn) {
-        let mut transformed_stmts = Vec::new();
-
-        for stmt in &func.block.stmts {
-            match stmt {
-                syn::Stmt::Local(local) => {
-                    if let Some(init) = &local.init {
-                        if let Some(transformed_stmt) = self.transform_env_expr(&init.expr) {
-                            let mut new_local = local.clone();
-                            new_local.init = Some(syn::LocalInit {
-                                eq_token: init.eq_token.clone(),
-                                expr: Box::new(syn::Expr::Verbatim(
-                                    quote::quote!(#transformed_stmt),
-                                )),
-                                diverge: init.diverge.clone(),
-                            });
-                            transformed_stmts.push(syn::Stmt::Local(new_local));
-                            continue;
-                        }
-                    }
-                    transformed_stmts.push(stmt.clone());
-                }
+    fn generate_host_code(
+        &mut self,
+        template: &Template,
+        host_file_path: &Path,
+    ) -> Result<(), CodegenError> {
+        let main_function = template
+            .syntax_tree
+            .items
+            .iter()
+            .find_map(|item| match item {
+                syn::Item::Fn(func) if func.sig.ident == "main" => Some(func),
+                _ => None,
+            })
+            .ok_or_else(|| {
+                CodegenError::new("`main` function not found")
+                    .in_file(&template.path)
+                    .with_help("the guest program is the `main` function of the template")
+            })?;
+
+        let io = GuestIo::collect(template, main_function, self.env.as_ref())?;
+
+        let env_code = self.env.generate_host_env(&io.writes);
+        let mut output_code = if io.raw_output {
+            self.env.generate_raw_output_decoding()
+        } else {
+            self.env.generate_output_decoding(&io.output_types)
+        };
+        for index in 1..=io.output_types.len() {
+            output_code.push_str(&format!("println!(\"Output: {{:?}}\", output{});\n", index));
+        }
 
-                syn::Stmt::Expr(expr, _) => {
-                    if let Some(transformed_stmt) = self.transform_env_expr(expr) {
-                        transformed_stmts.push(transformed_stmt);
-                    } else {
-                        transformed_stmts.push(stmt.clone());
-                    }
-                }
+        let host_template = self.env.get_host_template();
 
-                _ => transformed_stmts.push(stmt.clone()),
-            }
+        let mut generated_code = host_template.to_string();
+        let mut assignment_lines: Vec<String> =
+            io.inputs.iter().map(|input| input.assignment()).collect();
+        // log every generated input so the results record which parameters were benchmarked
+        for input in &io.inputs {
+            assignment_lines.push(format!(
+                "println!(\"Input parameter: {{}}\", {:?});",
+                format!("{} = {}", input.name, input.generator)
+            ));
         }
+        let assignment_lines = assignment_lines.join("\n");
+        generated_code = generated_code.replace("// INPUT_ASSIGNMENTS", &assignment_lines);
+        generated_code = generated_code.replace("// ENVIRONMENT_BUILDER", &env_code);
+        generated_code = generated_code.replace("// OUTPUT_DECODING", &output_code);
+        let check_code = generate_output_check(template, &io.output_types)?;
+        generated_code = generated_code.replace("// OUTPUT_CHECK", &check_code);
 
-        func.block.stmts = transformed_stmts;
+        write_atomically(host_file_path, generated_code)
     }
+}
 
-    fn transform_env_expr(&self, expr: &syn::Expr) -> Option<syn::Stmt> {
-        if let syn::Expr::Call(call) = expr {
-            if let syn::Expr::Path(path) = &*call.func {
-                let segments = &path.path.segments;
-
-                if segments.len() == 2 && segments[0].ident == "env" {
-                    match segments[1].ident.to_string().as_str() {
-                        "read" => {
-                            if call.args.is_empty() {
-                                return Some(self.env.read());
-                            }
-                        }
-                        "commit" => {
-                            if let Some(arg) = call.args.first() {
-                                if let syn::Expr::Path(arg_path) = arg {
-                                    let var_name = arg_path.path.segments[0].ident.to_string();
-                                    let stmt = self.env.commit(&var_name);
-                                    return Some(stmt);
-                                } else if let syn::Expr::Reference(ref_expr) = arg {
-                                    if let syn::Expr::Path(arg_path) = &*ref_expr.expr {
-                                        let var_name = arg_path.path.segments[0].ident.to_string();
-                                        let stmt = self.env.commit(&var_name);
-                                        return Some(stmt);
-                                    }
-                                }
-                            }
-                        }
-                        _ => {}
-                    }
-                }
-            }
-        }
+/// A test template, read and parsed once.
+pub(crate) struct Template {
+    pub path: PathBuf,
+    pub source: String,
+    pub syntax_tree: File,
+}
 
-        None
+impl Template {
+    fn load(path: &Path) -> Result<Template, CodegenError> {
+        let source = fs::read_to_string(path)
+            .map_err(|err| CodegenError::io("read the template", path, err))?;
+        let syntax_tree = parse_file(&source)
+            .map_err(|err| CodegenError::spanned(path, &source, err.span(), err.to_string()))?;
+        Ok(Template {
+            path: path.to_path_buf(),
+            source,
+            syntax_tree,
+        })
     }
 
-    fn generate_host_code(&self, input_path: &str, host_file_path: &str) {
-        let source_code = fs::read_to_string(input_path).expect("Failed to read the input file");
-        let syntax_tree = parse_file(&source_code).expect("Failed to parse the input file");
-        let main_function = syntax_tree
-            .items
-            .iter()
-            .find_map(|item| match item {
-                syn::Item::Fn(func) if func.sig.ident == "main" => Some(func),
-                _ => None,
-            })
-            .expect("Main function not found");
+    pub fn error(&self, span: Span, message: impl Into<String>) -> CodegenError {
+        CodegenError::spanned(&self.path, &self.source, span, message)
+    }
 
-        let source_lines: Vec<&str> = source_code.lines().collect();
-        let assignments = extract_read_assignments(main_function, &source_lines);
+    /// An error pointing at a byte range of a 1-based line, for code syn has no span for.
+    pub fn error_in_line(
+        &self,
+        line: usize,
+        range: Range<usize>,
+        message: impl Into<String>,
+    ) -> CodegenError {
+        let line_start: usize = self
+            .source
+            .split_inclusive('\n')
+            .take(line - 1)
+            .map(str::len)
+            .sum();
+        let range = line_start + range.start..line_start + range.end;
+        CodegenError::at_offset(&self.path, &self.source, range, message)
+    }
+}
 
-        let env_code = self.env.generate_host_env(&assignments);
+/// Write a generated file through a temporary file next to it that is renamed over it, so no
+/// file is ever left half-written.
+pub(crate) fn write_atomically(
+    path: &Path,
+    contents: impl AsRef<[u8]>,
+) -> Result<(), CodegenError> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    fs::write(&temporary, contents)
+        .and_then(|()| fs::rename(&temporary, path))
+        .map_err(|err| CodegenError::io("write", path, err))
+}
 
-        let host_template = self.env.get_host_template();
+/// Replace everything in `workspace` but its `target` directory with a copy of `project`.
+fn copy_project(project: &Path, workspace: &Path) -> Result<(), CodegenError> {
+    let clear = || -> io::Result<()> {
+        if !workspace.exists() {
+            return Ok(());
+        }
+        for entry in fs::read_dir(workspace)? {
+            let entry = entry?;
+            if entry.file_name() == "target" {
+                continue;
+            }
+            if entry.file_type()?.is_dir() {
+                fs::remove_dir_all(entry.path())?;
+            } else {
+                fs::remove_file(entry.path())?;
+            }
+        }
+        Ok(())
+    };
+    clear().map_err(|err| CodegenError::io("clear the workspace", workspace, err))?;
+    copy_dir(project, workspace).map_err(|err| CodegenError::io("copy the project", project, err))
+}
 
-        let mut generated_code = host_template.to_string();
-        let assignment_lines = assignments.join("\n");
-        generated_code = generated_code.replace("// INPUT_ASSIGNMENTS", &assignment_lines);
-        generated_code = generated_code.replace("// ENVIRONMENT_BUILDER", &env_code);
+/// Copy a directory without its build outputs.
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if name == "target" {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &to.join(&name))?;
+        } else {
+            fs::copy(entry.path(), to.join(&name))?;
+        }
+    }
+    Ok(())
+}
 
-        fs::write(host_file_path, generated_code).expect("Failed to write to the host file");
+/// Every `Cargo.toml` under `dir`, outside of `target` directories.
+fn find_manifests(dir: &Path) -> Result<Vec<PathBuf>, CodegenError> {
+    let mut manifests = Vec::new();
+    let entries =
+        fs::read_dir(dir).map_err(|err| CodegenError::io("read the workspace", dir, err))?;
+    for entry in entries {
+        let path = entry
+            .map_err(|err| CodegenError::io("read the workspace", dir, err))?
+            .path();
+        if path.is_dir() && !path.ends_with("target") {
+            manifests.extend(find_manifests(&path)?);
+        } else if path.ends_with("Cargo.toml") {
+            manifests.push(path);
+        }
     }
+    Ok(manifests)
+}
+
+/// Read and parse a generated host file. It is produced from the host templates, so a parse
+/// error here is a bug in the templates rather than in the test.
+fn read_host_file(host_file_path: &Path) -> Result<File, CodegenError> {
+    let content = fs::read_to_string(host_file_path)
+        .map_err(|err| CodegenError::io("read the host code", host_file_path, err))?;
+    parse_file(&content).map_err(|err| {
+        CodegenError::spanned(
+            host_file_path,
+            &content,
+            err.span(),
+            format!("generated host code does not parse: {}", err),
+        )
+    })
 }
 
 /// Check if an attribute list contains #[host]
-fn has_host_annotation(attrs: &[Attribute]) -> bool {
+pub(crate) fn has_host_annotation(attrs: &[Attribute]) -> bool {
     attrs.iter().any(|attr| attr.meta.path().is_ident("host"))
 }
 
-fn append_host_functions_to_syn_tree(input_path: &str, host_file_path: &str) {
-    let source_code =
-        fs::read_to_string(input_path).expect("Failed to read the input Rust source file");
-
-    let syntax_tree = parse_file(&source_code).expect("Failed to parse the input Rust source file");
-
-    let host_functions: Vec<ItemFn> = syntax_tree
+fn append_host_functions_to_syn_tree(
+    template: &Template,
+    host_file_path: &Path,
+) -> Result<LineMap, CodegenError> {
+    let host_functions: Vec<ItemFn> = template
+        .syntax_tree
         .items
+        .clone()
         .into_iter()
         .filter_map(|item| match item {
             Item::Fn(mut item_fn) if has_host_annotation(&item_fn.attrs) => {
@@ -450,32 +560,26 @@ fn append_host_functions_to_syn_tree(input_path: &str, host_file_path: &str) {
         })
         .collect();
 
-    let mut host_syntax_tree: File = match fs::read_to_string(host_file_path) {
-        Ok(content) => parse_file(&content).expect("Failed to parse the existing host file"),
-        Err(_) => File {
-            shebang: None,
-            items: Vec::new(),
-            attrs: Vec::new(),
-        },
-    };
+    let mut host_syntax_tree = read_host_file(host_file_path)?;
 
     host_syntax_tree
         .items
         .extend(host_functions.into_iter().map(Item::Fn));
 
-    let updated_code = format_syntax_tree(&host_syntax_tree);
+    let (updated_code, line_map) = render(&host_syntax_tree, template, host_file_path);
 
-    fs::write(host_file_path, updated_code).expect("Failed to write the updated host file");
+    write_atomically(host_file_path, updated_code)?;
+    Ok(line_map)
 }
 
-fn prepend_host_imports_to_syn_tree(input_path: &str, host_file_path: &str) {
-    let source_code =
-        fs::read_to_string(input_path).expect("Failed to read the input Rust source file");
-
-    let syntax_tree = parse_file(&source_code).expect("Failed to parse the input Rust source file");
-
-    let host_imports: Vec<Item> = syntax_tree
+fn prepend_host_imports_to_syn_tree(
+    template: &Template,
+    host_file_path: &Path,
+) -> Result<(), CodegenError> {
+    let host_imports: Vec<Item> = template
+        .syntax_tree
         .items
+        .clone()
         .into_iter()
         .filter_map(|item| match item {
             Item::Use(use_item) if has_host_annotation(&use_item.attrs) => {
@@ -485,14 +589,7 @@ fn prepend_host_imports_to_syn_tree(input_path: &str, host_file_path: &str) {
         })
         .collect();
 
-    let mut host_syntax_tree: File = match fs::read_to_string(host_file_path) {
-        Ok(content) => parse_file(&content).expect("Failed to parse the existing host file"),
-        Err(_) => File {
-            shebang: None,
-            items: Vec::new(),
-            attrs: Vec::new(),
-        },
-    };
+    let mut host_syntax_tree = read_host_file(host_file_path)?;
 
     // prepend without the #[host] attribute
     host_syntax_tree.items = host_imports
@@ -508,94 +605,77 @@ fn prepend_host_imports_to_syn_tree(input_path: &str, host_file_path: &str) {
         .chain(host_syntax_tree.items)
         .collect();
 
-    let updated_code = format_syntax_tree(&host_syntax_tree);
-
-    fs::write(host_file_path, updated_code).expect("Failed to write the updated host file");
-}
-
-fn format_syntax_tree(file: &File) -> String {
-    let mut code = String::new();
-
-    // Serialize each item in the syntax tree into its token stream
-    for item in &file.items {
-        code.push_str(&item.to_token_stream().to_string());
-        code.push('\n');
-    }
+    let updated_code = prettyplease::unparse(&host_syntax_tree);
 
-    code
+    write_atomically(host_file_path, updated_code)
 }
 
-/// Extract `env::read()` calls from the body of the `main` function and generate assignments
-fn extract_read_assignments(main_function: &ItemFn, source_lines: &[&str]) -> Vec<String> {
-    let mut assignments = Vec::new();
-    let mut current_line = 0;
-    let mut input_index = 1;
-
-    for stmt in &main_function.block.stmts {
-        while current_line < source_lines.len() && !source_lines[current_line].contains("env::read")
-        {
-            current_line += 1;
-        }
-
-        if let Stmt::Local(local) = stmt {
-            // Check if the statement has an initializer expression
-            if let Some(init) = &local.init {
-                let expr = &init.expr;
-                if is_env_read_call(expr) {
-                    // Extract function name and arguments from the comment
-                    if let Some((func_name, args)) =
-                        extract_function_from_comment(source_lines[current_line])
-                    {
-                        // Generate the assignment line
-                        if args.is_empty() {
-                            assignments
-                                .push(format!("let input{} = {}();", input_index, func_name));
-                        } else {
-                            assignments.push(format!(
-                                "let input{} = {}({});",
-                                input_index, func_name, args
-                            ));
-                        }
-                        input_index += 1;
-                    }
-                }
+/// Compare the decoded outputs against `#[host] fn expected()` if the template declares it.
+///
+/// `expected()` returns the single committed value, or a tuple with one element per commit.
+fn generate_output_check(
+    template: &Template,
+    output_types: &[String],
+) -> Result<String, CodegenError> {
+    let output_count = output_types.len();
+    let Some(expected) = template
+        .syntax_tree
+        .items
+        .iter()
+        .find_map(|item| match item {
+            Item::Fn(func) if func.sig.ident == "expected" && has_host_annotation(&func.attrs) => {
+                Some(func)
             }
-        }
+            _ => None,
+        })
+    else {
+        return Ok(String::new());
+    };
 
-        current_line += 1;
+    if output_count == 0 {
+        return Err(template.error(
+            expected.sig.ident.span(),
+            "`expected()` is declared, but `main` does not commit any value",
+        ));
     }
-
-    assignments
-}
-/// Check if an expression is an `env::read()` call
-fn is_env_read_call(expr: &Expr) -> bool {
-    println!(
-        "Checking if expression is env::read() call: {}",
-        expr.to_token_stream()
-    );
-    if let Expr::Call(call_expr) = expr {
-        if let Expr::Path(path) = call_expr.func.as_ref() {
-            // Match the specific `env::read` path
-            println!("{}", path.to_token_stream().to_string() == "env :: read");
-            return path.to_token_stream().to_string() == "env :: read";
+    if let ReturnType::Type(_, ty) = &expected.sig.output {
+        if let Type::Tuple(tuple) = &**ty {
+            // a single commit of a tuple is compared as a whole
+            let commits_a_tuple = match output_types {
+                [output_type] => matches!(
+                    syn::parse_str::<Type>(output_type),
+                    Ok(Type::Tuple(output)) if output.elems.len() == tuple.elems.len()
+                ),
+                _ => false,
+            };
+            if tuple.elems.len() != output_count && !commits_a_tuple {
+                return Err(template
+                    .error(
+                        ty.span(),
+                        format!(
+                            "`expected()` returns {} values, but `main` commits {}",
+                            tuple.elems.len(),
+                            output_count
+                        ),
+                    )
+                    .with_help("return a tuple with one element per `env::commit`, in order"));
+            }
         }
     }
-    println!("Not an env::read() call");
-    false
-}
 
-/// Extract the function name and arguments from a comment
-fn extract_function_from_comment(line: &str) -> Option<(String, String)> {
-    // Look for a comment in the format `// #function_name(args)`
-    if let Some(comment_start) = line.find("// #") {
-        let comment = &line[comment_start + 4..];
-        if let Some(open_paren_pos) = comment.find('(') {
-            let func_name = &comment[..open_paren_pos].trim();
-            let args = &comment[open_paren_pos + 1..comment.len() - 1].trim(); // Exclude closing ')'
-            return Some((func_name.to_string(), args.to_string()));
-        }
-    }
-    None
+    let names: Vec<String> = (1..=output_count)
+        .map(|index| format!("output{}", index))
+        .collect();
+    let outputs = if output_count == 1 {
+        names[0].clone()
+    } else {
+        format!("({})", names.join(", "))
+    };
+    Ok(format!(
+        "let output_check = if {} == expected() {{ \"passed\" }} else {{ \"failed\" }};\n\
+         println!(\"Output check: {{}}\", output_check);\n",
+        outputs
+    ))
 }
 
 // strip the given attribute from the syntax tree
@@ -628,38 +708,36 @@ fn process_items_with_attribute(
             let mut keep_item = true;
 
             match &item {
-                Item::Fn(func) => {
+                Item::Fn(func)
                     if func
                         .attrs
                         .iter()
-                        .any(|attr| attr.path().is_ident(attribute_name))
-                    {
-                        if strip_item {
-                            keep_item = false;
-                        } else if strip_attribute {
-                            let mut new_func = func.clone();
-                            new_func
-                                .attrs
-                                .retain(|attr| !attr.path().is_ident(attribute_name));
-                            return Some(Item::Fn(new_func));
-                        }
+                        .any(|attr| attr.path().is_ident(attribute_name)) =>
+                {
+                    if strip_item {
+                        keep_item = false;
+                    } else if strip_attribute {
+                        let mut new_func = func.clone();
+                        new_func
+                            .attrs
+                            .retain(|attr| !attr.path().is_ident(attribute_name));
+                        return Some(Item::Fn(new_func));
                     }
                 }
-                Item::Use(use_item) => {
+                Item::Use(use_item)
                     if use_item
                         .attrs
                         .iter()
-                        .any(|attr| attr.path().is_ident(attribute_name))
-                    {
-                        if strip_item {
-                            keep_item = false;
-                        } else if strip_attribute {
-                            let mut new_use_item = use_item.clone();
-                            new_use_item
-                                .attrs
-                                .retain(|attr| !attr.path().is_ident(attribute_name));
-                            return Some(Item::Use(new_use_item));
-                        }
+                        .any(|attr| attr.path().is_ident(attribute_name)) =>
+                {
+                    if strip_item {
+                        keep_item = false;
+                    } else if strip_attribute {
+                        let mut new_use_item = use_item.clone();
+                        new_use_item
+                            .attrs
+                            .retain(|attr| !attr.path().is_ident(attribute_name));
+                        return Some(Item::Use(new_use_item));
                     }
                 }
                 _ => {}
@@ -673,3 +751,73 @@ fn process_items_with_attribute(
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output_check(source: &str, output_types: &[&str]) -> Result<String, String> {
+        let template = Template {
+            path: PathBuf::from("template.rs"),
+            source: source.to_string(),
+            syntax_tree: parse_file(source).unwrap(),
+        };
+        let output_types: Vec<String> = output_types.iter().map(|ty| ty.to_string()).collect();
+        generate_output_check(&template, &output_types).map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn compares_every_commit_with_the_expected_tuple() {
+        let check = output_check(
+            "#[host] fn expected() -> (u32, bool) { (1, true) }",
+            &["u32", "bool"],
+        )
+        .unwrap();
+        assert!(
+            check.contains("if (output1, output2) == expected()"),
+            "{}",
+            check
+        );
+    }
+
+    #[test]
+    fn compares_a_committed_tuple_as_a_whole() {
+        let check = output_check(
+            "#[host] fn expected() -> (u32, bool) { (1, true) }",
+            &["(u32 , bool ,)"],
+        )
+        .unwrap();
+        assert!(check.contains("if output1 == expected()"), "{}", check);
+    }
+
+    #[test]
+    fn rejects_a_tuple_with_more_values_than_commits() {
+        let err =
+            output_check("#[host] fn expected() -> (u32, u32) { (1, 2) }", &["u32"]).unwrap_err();
+        assert!(
+            err.contains("`expected()` returns 2 values, but `main` commits 1"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn rejects_a_tuple_with_fewer_values_than_commits() {
+        let err = output_check(
+            "#[host] fn expected() -> (u32, u32) { (1, 2) }",
+            &["u32", "u32", "u32"],
+        )
+        .unwrap_err();
+        assert!(
+            err.contains("`expected()` returns 2 values, but `main` commits 3"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn rejects_expected_without_commits() {
+        let err = output_check("#[host] fn expected() -> u32 { 1 }", &[]).unwrap_err();
+        assert!(err.contains("does not commit any value"), "{}", err);
+    }
+}
@@ -0,0 +1,105 @@
+//! The `zkbench.toml` configuration.
+//!
+//! It names the project of every zkVM and where its guest and host crates are, so codegen and
+//! the runner resolve every path from it instead of from the directory they are started in. The
+//! file is looked up from the current directory upwards, then next to the `tests` crate it was
+//! built from.
+
+use crate::diagnostics::CodegenError;
+use crate::env_adapters::Layout;
+use crate::runner::Vm;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const FILE_NAME: &str = "zkbench.toml";
+
+/// The paths are resolved from the directory of the file when it is loaded.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The test templates, one directory per test
+    pub templates: PathBuf,
+    pub results: PathBuf,
+    /// Where the per-test copies of the projects are made
+    pub build: PathBuf,
+    risc0: Backend,
+    sp1: Backend,
+    valida: Backend,
+    native: Backend,
+}
+
+/// A backend section. Its layout keys are all required, so a misspelled key is reported as a
+/// missing one.
+#[derive(Debug, Clone, Deserialize)]
+struct Backend {
+    root: PathBuf,
+    #[serde(flatten)]
+    layout: Layout,
+}
+
+impl Config {
+    /// Load the closest `zkbench.toml`, see the module documentation.
+    pub fn find() -> Result<Config, CodegenError> {
+        let current_dir = std::env::current_dir().map_err(|err| {
+            CodegenError::new(format!("cannot read the current directory: {}", err))
+        })?;
+        let built_from = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+        let path = current_dir
+            .ancestors()
+            .chain([built_from.as_path()])
+            .map(|dir| dir.join(FILE_NAME))
+            .find(|path| path.is_file())
+            .ok_or_else(|| {
+                CodegenError::new(format!(
+                    "no {} in the current directory or its parents",
+                    FILE_NAME
+                ))
+                .with_help("run from the benchmarking repository or pass `--config <path>`")
+            })?;
+        Config::load(&path)
+    }
+
+    pub fn load(path: &Path) -> Result<Config, CodegenError> {
+        let source = fs::read_to_string(path).map_err(|err| CodegenError::io("read", path, err))?;
+        let mut config: Config = toml_edit::de::from_str(&source).map_err(|err| {
+            let message = err.message().trim_end().to_string();
+            match err.span() {
+                Some(range) => CodegenError::at_offset(path, &source, range, message),
+                None => CodegenError::new(message).in_file(path),
+            }
+        })?;
+
+        let dir = path.parent().unwrap_or(Path::new("."));
+        for path in [
+            &mut config.templates,
+            &mut config.results,
+            &mut config.build,
+            &mut config.risc0.root,
+            &mut config.sp1.root,
+            &mut config.valida.root,
+            &mut config.native.root,
+        ] {
+            *path = dir.join(&*path);
+        }
+        Ok(config)
+    }
+
+    /// The root of the checked-in project of `vm`.
+    pub fn project(&self, vm: Vm) -> &Path {
+        &self.backend(vm).root
+    }
+
+    pub fn layout(&self, vm: Vm) -> &Layout {
+        &self.backend(vm).layout
+    }
+
+    fn backend(&self, vm: Vm) -> &Backend {
+        match vm {
+            Vm::Risc0 => &self.risc0,
+            Vm::Sp1 => &self.sp1,
+            Vm::Valida => &self.valida,
+            Vm::Native => &self.native,
+        }
+    }
+}
@@ -0,0 +1,177 @@
+//! Errors in test templates, rendered like rustc diagnostics.
+//!
+//! ```text
+//! error: missing input generator for `env::read()`
+//!   --> ./test_templates/rsa/rsa.rs:17:28
+//!    |
+//! 17 |     let pk_der: Vec<u8> = env::read();
+//!    |                           ^^^^^^^^^^^
+//!    |
+//!    = help: name the host function that generates the input with `#[input(gen = "input()")]`
+//! ```
+//!
+//! Problems that do not stop codegen, like a template dependency replacing an incompatible one of
+//! the project, are rendered the same way as warnings.
+
+use proc_macro2::Span;
+use std::fmt;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub struct CodegenError {
+    level: Level,
+    message: String,
+    path: Option<PathBuf>,
+    location: Option<Box<Location>>,
+    help: Option<String>,
+}
+
+#[derive(Debug)]
+enum Level {
+    Error,
+    Warning,
+}
+
+/// The part of a source line an error points at.
+#[derive(Debug)]
+struct Location {
+    /// 1-based
+    line: usize,
+    /// 0-based, in characters
+    column: usize,
+    width: usize,
+    source_line: String,
+}
+
+impl CodegenError {
+    pub fn new(message: impl Into<String>) -> CodegenError {
+        CodegenError {
+            level: Level::Error,
+            message: message.into(),
+            path: None,
+            location: None,
+            help: None,
+        }
+    }
+
+    /// An error about a whole file or directory.
+    pub fn in_file(mut self, path: &Path) -> CodegenError {
+        self.path = Some(path.to_path_buf());
+        self
+    }
+
+    /// An error pointing at the code a syn span covers.
+    pub fn spanned(path: &Path, source: &str, span: Span, message: impl Into<String>) -> Self {
+        let start = span.start();
+        let end = span.end();
+        let source_line = source
+            .lines()
+            .nth(start.line.saturating_sub(1))
+            .unwrap_or("");
+        let width = if end.line == start.line {
+            end.column.saturating_sub(start.column)
+        } else {
+            source_line.chars().count().saturating_sub(start.column)
+        };
+        CodegenError {
+            location: Some(Box::new(Location {
+                line: start.line,
+                column: start.column,
+                width: width.max(1),
+                source_line: source_line.to_string(),
+            })),
+            ..CodegenError::new(message).in_file(path)
+        }
+    }
+
+    /// An error pointing at a byte range of a file, as reported by the TOML parser.
+    pub fn at_offset(
+        path: &Path,
+        source: &str,
+        range: Range<usize>,
+        message: impl Into<String>,
+    ) -> Self {
+        let before = &source[..range.start.min(source.len())];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        let column = before[line_start..].chars().count();
+        let source_line = source.lines().nth(line - 1).unwrap_or("");
+        let width = source.get(range.clone()).map_or(1, |covered| {
+            covered.lines().next().unwrap_or("").chars().count()
+        });
+        CodegenError {
+            location: Some(Box::new(Location {
+                line,
+                column,
+                width: width.max(1),
+                source_line: source_line.to_string(),
+            })),
+            ..CodegenError::new(message).in_file(path)
+        }
+    }
+
+    /// A file that could not be read or written.
+    pub fn io(action: &str, path: &Path, err: io::Error) -> CodegenError {
+        CodegenError::new(format!("failed to {}: {}", action, err)).in_file(path)
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> CodegenError {
+        self.help = Some(help.into());
+        self
+    }
+
+    /// Report the problem as a warning, codegen goes on after printing it.
+    pub fn as_warning(mut self) -> CodegenError {
+        self.level = Level::Warning;
+        self
+    }
+}
+
+impl fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = match self.level {
+            Level::Error => "error",
+            Level::Warning => "warning",
+        };
+        writeln!(f, "{}: {}", level, self.message)?;
+        let gutter = self
+            .location
+            .as_ref()
+            .map_or(1, |location| location.line.to_string().len());
+        let pad = " ".repeat(gutter);
+
+        if let Some(path) = &self.path {
+            match &self.location {
+                Some(location) => writeln!(
+                    f,
+                    "{}--> {}:{}:{}",
+                    pad,
+                    path.display(),
+                    location.line,
+                    location.column + 1
+                )?,
+                None => writeln!(f, "{}--> {}", pad, path.display())?,
+            }
+        }
+        if let Some(location) = &self.location {
+            writeln!(f, "{} |", pad)?;
+            writeln!(f, "{} | {}", location.line, location.source_line)?;
+            writeln!(
+                f,
+                "{} | {}{}",
+                pad,
+                " ".repeat(location.column),
+                "^".repeat(location.width)
+            )?;
+        }
+        if let Some(help) = &self.help {
+            writeln!(f, "{} |", pad)?;
+            writeln!(f, "{} = help: {}", pad, help)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CodegenError {}
@@ -1,14 +1,49 @@
+use serde::Deserialize;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
-use quote::ToTokens;
-use std::fmt;
-use syn::{self, ExprCall};
-
+/// The operations a template can call on `env`, mapped to the SDK of a zkVM, and the host code
+/// that feeds and decodes them.
+///
+/// Arguments passed to the guest operations can be borrowed as they are.
 pub trait CodeEnv {
-    fn read(&self) -> syn::Stmt;
-    fn commit(&self, var_name: &str) -> syn::Stmt;
+    /// Read a private input, deserialized from what the host wrote
+    fn read(&self) -> syn::Expr;
+    /// Read a private input as raw bytes, a `Vec<u8>` the host wrote without serializing it
+    fn read_slice(&self) -> syn::Expr;
+    /// Read a public input: a value read like `read` and committed right away, so the verifier
+    /// sees it
+    fn read_public(&self) -> syn::Expr {
+        let read = self.read();
+        let commit = self.commit(&syn::parse_quote!(value));
+        syn::parse_quote!({
+            let value = #read;
+            #commit;
+            value
+        })
+    }
+    fn commit(&self, value: &syn::Expr) -> syn::Expr;
+    /// Commit bytes as they are, without serializing them
+    fn commit_slice(&self, value: &syn::Expr) -> syn::Expr;
+    /// Write bytes to the standard output of the guest
+    fn write_stdout(&self, value: &syn::Expr) -> syn::Expr;
+    /// Write bytes to the standard error of the guest
+    fn write_stderr(&self, value: &syn::Expr) -> syn::Expr;
+    /// The `env::` functions the zkVM has no equivalent for, with the reason. A template that
+    /// calls one of them is rejected before any code is generated.
+    fn unsupported(&self) -> &'static [(&'static str, &'static str)] {
+        &[]
+    }
+    /// The name of the zkVM, for diagnostics
+    fn name(&self) -> &'static str;
     fn import(&self) -> Vec<syn::Item>;
-    fn generate_host_env(&self, assignments: &[String]) -> String;
+    /// Write the generated inputs for the guest, in the order it reads them.
+    fn generate_host_env(&self, inputs: &[InputWrite]) -> String;
+    /// Decode the committed values into `output1`, `output2`, ... with the given types, in
+    /// commit order.
+    fn generate_output_decoding(&self, output_types: &[String]) -> String;
+    /// Decode the bytes of a single `commit_slice` into `output1`.
+    fn generate_raw_output_decoding(&self) -> String;
     fn get_host_template(&self) -> String;
     fn get_file_copy_destination(&self) -> String;
     fn get_host_cargo_toml_path(&self) -> String;
@@ -17,16 +52,108 @@ pub trait CodeEnv {
     fn get_guest_output_dir(&self) -> String;
     fn get_host_output_dir(&self) -> String;
     fn get_workspace_cargo_toml_path(&self) -> String;
+    /// The root directory of the project codegen writes into
+    fn get_project_dir(&self) -> String;
+}
+
+/// Where the parts of a zkVM project are, relative to its root. Read from the backend sections
+/// of `zkbench.toml`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Layout {
+    /// The manifest of the workspace, which gets the precompile patches
+    pub workspace_manifest: PathBuf,
+    pub guest_manifest: PathBuf,
+    /// Where the guest `main.rs` is generated
+    pub guest_src: PathBuf,
+    pub host_manifest: PathBuf,
+    /// Where the host `main.rs` is generated and the additional files of a template are copied
+    pub host_src: PathBuf,
+}
+
+fn in_project(root: &Path, relative: &Path) -> String {
+    root.join(relative).to_string_lossy().into_owned()
+}
+
+/// How the host writes the generated inputs.
+pub enum InputWrite {
+    /// A read that runs once
+    Once(InputValue),
+    /// The reads of one loop. Each variable holds one value per iteration, and they are written
+    /// interleaved, in the order the loop reads them.
+    Repeated {
+        count: usize,
+        values: Vec<InputValue>,
+    },
+}
+
+/// A host variable holding a generated input.
+pub struct InputValue {
+    pub name: String,
+    /// Written as raw bytes, for `env::read_slice()`
+    pub raw: bool,
+}
+
+/// Write every input in read order. `write` and `write_raw` turn a place expression holding a
+/// value, or its bytes, into statements.
+fn write_inputs(
+    inputs: &[InputWrite],
+    write: impl Fn(&str) -> String,
+    write_raw: impl Fn(&str) -> String,
+) -> String {
+    let write_value = |value: &InputValue, place: String| {
+        if value.raw {
+            write_raw(&place)
+        } else {
+            write(&place)
+        }
+    };
+    let mut code = String::new();
+    for input in inputs {
+        match input {
+            InputWrite::Once(value) => code.push_str(&write_value(value, value.name.clone())),
+            InputWrite::Repeated { count, values } => {
+                code.push_str(&format!("for index in 0..{} {{\n", count));
+                for value in values {
+                    code.push_str(&write_value(value, format!("{}[index]", value.name)));
+                }
+                code.push_str("}\n");
+            }
+        }
+    }
+    code
 }
 
+/// Placeholder environment imported by the test templates
+/// (`use crate::env_adapters::NotImplementedEnv as env;`).
+#[allow(dead_code)]
 pub struct NotImplementedEnv;
 
 impl CodeEnv for NotImplementedEnv {
-    fn read(&self) -> syn::Stmt {
+    fn name(&self) -> &'static str {
+        "none"
+    }
+
+    fn read(&self) -> syn::Expr {
+        unimplemented!("Please choose the appropriate environment");
+    }
+
+    fn read_slice(&self) -> syn::Expr {
+        unimplemented!("Please choose the appropriate environment");
+    }
+
+    fn commit(&self, _value: &syn::Expr) -> syn::Expr {
+        unimplemented!("Please choose the appropriate environment");
+    }
+
+    fn commit_slice(&self, _value: &syn::Expr) -> syn::Expr {
+        unimplemented!("Please choose the appropriate environment");
+    }
+
+    fn write_stdout(&self, _value: &syn::Expr) -> syn::Expr {
         unimplemented!("Please choose the appropriate environment");
     }
 
-    fn commit(&self, _var_name: &str) -> syn::Stmt {
+    fn write_stderr(&self, _value: &syn::Expr) -> syn::Expr {
         unimplemented!("Please choose the appropriate environment");
     }
 
@@ -34,7 +161,15 @@ impl CodeEnv for NotImplementedEnv {
         unimplemented!("Please choose the appropriate environment");
     }
 
-    fn generate_host_env(&self, assignments: &[String]) -> String {
+    fn generate_host_env(&self, _inputs: &[InputWrite]) -> String {
+        unimplemented!("Please choose the appropriate environment");
+    }
+
+    fn generate_output_decoding(&self, _output_types: &[String]) -> String {
+        unimplemented!("Please choose the appropriate environment");
+    }
+
+    fn generate_raw_output_decoding(&self) -> String {
         unimplemented!("Please choose the appropriate environment");
     }
 
@@ -69,19 +204,49 @@ impl CodeEnv for NotImplementedEnv {
     fn get_workspace_cargo_toml_path(&self) -> String {
         unimplemented!("Please choose the appropriate environment");
     }
+
+    fn get_project_dir(&self) -> String {
+        unimplemented!("Please choose the appropriate environment");
+    }
 }
 
-pub struct Sp1Env;
+pub struct Sp1Env {
+    /// The root of the SP1 project codegen writes into
+    pub root: PathBuf,
+    pub layout: Layout,
+}
 
 impl CodeEnv for Sp1Env {
-    fn read(&self) -> syn::Stmt {
-        let code = format!("sp1_zkvm::io::read();",);
-        syn::parse_str(&code).unwrap()
+    fn name(&self) -> &'static str {
+        "sp1"
+    }
+
+    fn get_project_dir(&self) -> String {
+        self.root.to_string_lossy().into_owned()
+    }
+
+    fn read(&self) -> syn::Expr {
+        syn::parse_quote!(sp1_zkvm::io::read())
+    }
+
+    fn read_slice(&self) -> syn::Expr {
+        syn::parse_quote!(sp1_zkvm::io::read_vec())
+    }
+
+    fn commit(&self, value: &syn::Expr) -> syn::Expr {
+        syn::parse_quote!(sp1_zkvm::io::commit(&#value))
+    }
+
+    fn commit_slice(&self, value: &syn::Expr) -> syn::Expr {
+        syn::parse_quote!(sp1_zkvm::io::commit_slice(&#value[..]))
     }
 
-    fn commit(&self, var_name: &str) -> syn::Stmt {
-        let code = format!("sp1_zkvm::io::commit(&{0});", var_name);
-        syn::parse_str(&code).unwrap()
+    fn write_stdout(&self, value: &syn::Expr) -> syn::Expr {
+        syn::parse_quote!(sp1_zkvm::io::write(1, &#value[..]))
+    }
+
+    fn write_stderr(&self, value: &syn::Expr) -> syn::Expr {
+        syn::parse_quote!(sp1_zkvm::io::write(2, &#value[..]))
     }
 
     fn import(&self) -> Vec<syn::Item> {
@@ -91,29 +256,46 @@ impl CodeEnv for Sp1Env {
         ]
     }
 
-    fn generate_host_env(&self, assignments: &[String]) -> String {
+    fn generate_host_env(&self, inputs: &[InputWrite]) -> String {
         let mut builder_code = String::from("let mut stdin = SP1Stdin::new();\n");
-        for assignment in assignments {
-            let var_name = assignment.split_whitespace().nth(1).unwrap_or("");
-            builder_code.push_str(&format!("stdin.write(&{});\n", var_name));
-        }
+        builder_code.push_str(&write_inputs(
+            inputs,
+            |value| format!("stdin.write(&{});\n", value),
+            |bytes| format!("stdin.write_vec({}[..].to_vec());\n", bytes),
+        ));
         builder_code
     }
 
+    fn generate_output_decoding(&self, output_types: &[String]) -> String {
+        let mut decoding_code = String::new();
+        for (index, output_type) in output_types.iter().enumerate() {
+            decoding_code.push_str(&format!(
+                "let output{} = proof.public_values.read::<{}>();\n",
+                index + 1,
+                output_type
+            ));
+        }
+        decoding_code
+    }
+
+    fn generate_raw_output_decoding(&self) -> String {
+        String::from("let output1: Vec<u8> = proof.public_values.as_slice().to_vec();\n")
+    }
+
     fn get_host_template(&self) -> String {
         String::from(include_str!("../host_templates/sp1.rs"))
     }
 
     fn get_file_copy_destination(&self) -> String {
-        String::from("../sp1_benchmarks/sp1_project/script/src/bin")
+        in_project(&self.root, &self.layout.host_src)
     }
 
     fn get_host_cargo_toml_path(&self) -> String {
-        String::from("../sp1_benchmarks/sp1_project/script/Cargo.toml")
+        in_project(&self.root, &self.layout.host_manifest)
     }
 
     fn get_guest_cargo_toml_path(&self) -> String {
-        String::from("../sp1_benchmarks/sp1_project/program/Cargo.toml")
+        in_project(&self.root, &self.layout.guest_manifest)
     }
 
     fn get_available_patches(&self) -> HashMap<String, String> {
@@ -129,29 +311,55 @@ impl CodeEnv for Sp1Env {
     }
 
     fn get_guest_output_dir(&self) -> String {
-        String::from("../sp1_benchmarks/sp1_project/program/src")
+        in_project(&self.root, &self.layout.guest_src)
     }
 
     fn get_host_output_dir(&self) -> String {
-        String::from("../sp1_benchmarks/sp1_project/script/src/bin")
+        in_project(&self.root, &self.layout.host_src)
     }
 
     fn get_workspace_cargo_toml_path(&self) -> String {
-        String::from("../sp1_benchmarks/sp1_project/Cargo.toml")
+        in_project(&self.root, &self.layout.workspace_manifest)
     }
 }
 
-pub struct Risc0Env;
+pub struct Risc0Env {
+    /// The root of the RISC Zero project codegen writes into
+    pub root: PathBuf,
+    pub layout: Layout,
+}
 
 impl CodeEnv for Risc0Env {
-    fn read(&self) -> syn::Stmt {
-        let code = format!("env::read();");
-        syn::parse_str(&code).unwrap()
+    fn name(&self) -> &'static str {
+        "risc0"
+    }
+
+    fn get_project_dir(&self) -> String {
+        self.root.to_string_lossy().into_owned()
+    }
+
+    fn read(&self) -> syn::Expr {
+        syn::parse_quote!(env::read())
+    }
+
+    fn read_slice(&self) -> syn::Expr {
+        syn::parse_quote!(env::read_frame())
+    }
+
+    fn commit(&self, value: &syn::Expr) -> syn::Expr {
+        syn::parse_quote!(env::commit(&#value))
+    }
+
+    fn commit_slice(&self, value: &syn::Expr) -> syn::Expr {
+        syn::parse_quote!(env::commit_slice(&#value[..]))
+    }
+
+    fn write_stdout(&self, value: &syn::Expr) -> syn::Expr {
+        syn::parse_quote!(std::io::Write::write_all(&mut env::stdout(), &#value[..]).unwrap())
     }
 
-    fn commit(&self, var_name: &str) -> syn::Stmt {
-        let code = format!("env::commit(&{0});", var_name);
-        syn::parse_str(&code).unwrap()
+    fn write_stderr(&self, value: &syn::Expr) -> syn::Expr {
+        syn::parse_quote!(std::io::Write::write_all(&mut env::stderr(), &#value[..]).unwrap())
     }
 
     fn import(&self) -> Vec<syn::Item> {
@@ -160,31 +368,56 @@ impl CodeEnv for Risc0Env {
         )]
     }
 
-    fn generate_host_env(&self, assignments: &[String]) -> String {
-        let mut builder_code = String::from("ExecutorEnv::builder()\n");
-        for assignment in assignments {
-            let var_name = assignment.split_whitespace().nth(1).unwrap_or("");
-            builder_code.push_str(&format!("    .write(&{})\n", var_name));
-            builder_code.push_str("    .unwrap()\n");
-        }
-        builder_code.push_str("    .build()\n    .unwrap()");
+    fn generate_host_env(&self, inputs: &[InputWrite]) -> String {
+        let mut builder_code = String::from("{\nlet mut builder = ExecutorEnv::builder();\n");
+        builder_code.push_str(&write_inputs(
+            inputs,
+            |value| format!("builder.write(&{}).unwrap();\n", value),
+            |bytes| format!("builder.write_frame(&{}[..]);\n", bytes),
+        ));
+        builder_code.push_str("builder.build().unwrap()\n}");
         builder_code
     }
 
+    fn generate_output_decoding(&self, output_types: &[String]) -> String {
+        // the journal is one stream, so several commits are decoded together as a tuple
+        match output_types {
+            [] => String::new(),
+            [output_type] => format!(
+                "let output1: {} = receipt.journal.decode().unwrap();\n",
+                output_type
+            ),
+            _ => {
+                let names: Vec<String> = (1..=output_types.len())
+                    .map(|index| format!("output{}", index))
+                    .collect();
+                format!(
+                    "let ({}): ({}) = receipt.journal.decode().unwrap();\n",
+                    names.join(", "),
+                    output_types.join(", ")
+                )
+            }
+        }
+    }
+
+    fn generate_raw_output_decoding(&self) -> String {
+        String::from("let output1: Vec<u8> = receipt.journal.bytes.clone();\n")
+    }
+
     fn get_host_template(&self) -> String {
         String::from(include_str!("../host_templates/risc_zero.rs"))
     }
 
     fn get_file_copy_destination(&self) -> String {
-        String::from("../risc0_benchmarks/test_project/host/src")
+        in_project(&self.root, &self.layout.host_src)
     }
 
     fn get_host_cargo_toml_path(&self) -> String {
-        String::from("../risc0_benchmarks/test_project/host/Cargo.toml")
+        in_project(&self.root, &self.layout.host_manifest)
     }
 
     fn get_guest_cargo_toml_path(&self) -> String {
-        String::from("../risc0_benchmarks/test_project/methods/guest/Cargo.toml")
+        in_project(&self.root, &self.layout.guest_manifest)
     }
 
     fn get_available_patches(&self) -> HashMap<String, String> {
@@ -229,14 +462,240 @@ impl CodeEnv for Risc0Env {
     }
 
     fn get_guest_output_dir(&self) -> String {
-        String::from("../risc0_benchmarks/test_project/methods/guest/src")
+        in_project(&self.root, &self.layout.guest_src)
+    }
+
+    fn get_host_output_dir(&self) -> String {
+        in_project(&self.root, &self.layout.host_src)
+    }
+
+    fn get_workspace_cargo_toml_path(&self) -> String {
+        in_project(&self.root, &self.layout.workspace_manifest)
+    }
+}
+
+pub struct ValidaEnv {
+    /// The root of the Valida project codegen writes into
+    pub root: PathBuf,
+    pub layout: Layout,
+}
+
+impl CodeEnv for ValidaEnv {
+    fn name(&self) -> &'static str {
+        "valida"
+    }
+
+    fn get_project_dir(&self) -> String {
+        self.root.to_string_lossy().into_owned()
+    }
+
+    fn read(&self) -> syn::Expr {
+        syn::parse_quote!(valida_io::read())
+    }
+
+    fn read_slice(&self) -> syn::Expr {
+        syn::parse_quote!(valida_io::read_slice())
+    }
+
+    fn commit(&self, value: &syn::Expr) -> syn::Expr {
+        syn::parse_quote!(valida_io::commit(&#value))
+    }
+
+    fn commit_slice(&self, value: &syn::Expr) -> syn::Expr {
+        syn::parse_quote!(valida_io::commit_slice(&#value[..]))
+    }
+
+    fn write_stdout(&self, _value: &syn::Expr) -> syn::Expr {
+        unreachable!("`env::write_stdout` is rejected for valida")
+    }
+
+    fn write_stderr(&self, _value: &syn::Expr) -> syn::Expr {
+        unreachable!("`env::write_stderr` is rejected for valida")
+    }
+
+    fn unsupported(&self) -> &'static [(&'static str, &'static str)] {
+        &[
+            (
+                "write_stdout",
+                "the output of a valida program carries the committed values, \
+                 anything else written to it would be decoded as an output",
+            ),
+            ("write_stderr", "valida programs have no error stream"),
+        ]
+    }
+
+    fn import(&self) -> Vec<syn::Item> {
+        vec![
+            syn::Item::Verbatim(syn::parse_str("#![no_main]").unwrap()),
+            syn::Item::Macro(syn::parse_str("valida_rs::entrypoint!(main);").unwrap()),
+        ]
+    }
+
+    fn generate_host_env(&self, inputs: &[InputWrite]) -> String {
+        let mut builder_code = String::from("let mut stdin = String::new();\n");
+        // Valida only has a line based stdin, so raw bytes are sent as a JSON array as well
+        let write = |value: &str| {
+            format!(
+                "stdin.push_str(&serde_json::to_string(&{}).unwrap());\nstdin.push('\\n');\n",
+                value
+            )
+        };
+        builder_code.push_str(&write_inputs(inputs, write, |bytes| {
+            write(&format!("{}[..]", bytes))
+        }));
+        builder_code
+    }
+
+    fn generate_output_decoding(&self, output_types: &[String]) -> String {
+        let mut decoding_code = String::new();
+        for (index, output_type) in output_types.iter().enumerate() {
+            decoding_code.push_str(&format!(
+                "let output{}: {} = serde_json::from_str(output_lines.next().unwrap()).unwrap();\n",
+                index + 1,
+                output_type
+            ));
+        }
+        decoding_code
+    }
+
+    fn generate_raw_output_decoding(&self) -> String {
+        self.generate_output_decoding(&[String::from("Vec<u8>")])
+    }
+
+    fn get_host_template(&self) -> String {
+        String::from(include_str!("../host_templates/valida.rs"))
+    }
+
+    fn get_file_copy_destination(&self) -> String {
+        in_project(&self.root, &self.layout.host_src)
+    }
+
+    fn get_host_cargo_toml_path(&self) -> String {
+        in_project(&self.root, &self.layout.host_manifest)
+    }
+
+    fn get_guest_cargo_toml_path(&self) -> String {
+        in_project(&self.root, &self.layout.guest_manifest)
+    }
+
+    fn get_available_patches(&self) -> HashMap<String, String> {
+        // Valida has no accelerated forks of crates.io dependencies yet
+        HashMap::new()
+    }
+
+    fn get_guest_output_dir(&self) -> String {
+        in_project(&self.root, &self.layout.guest_src)
+    }
+
+    fn get_host_output_dir(&self) -> String {
+        in_project(&self.root, &self.layout.host_src)
+    }
+
+    fn get_workspace_cargo_toml_path(&self) -> String {
+        in_project(&self.root, &self.layout.workspace_manifest)
+    }
+}
+
+pub struct NativeEnv {
+    /// The root of the native project codegen writes into
+    pub root: PathBuf,
+    pub layout: Layout,
+}
+
+impl CodeEnv for NativeEnv {
+    fn name(&self) -> &'static str {
+        "native"
+    }
+
+    fn get_project_dir(&self) -> String {
+        self.root.to_string_lossy().into_owned()
+    }
+
+    fn read(&self) -> syn::Expr {
+        syn::parse_quote!(native_runtime::read())
+    }
+
+    fn read_slice(&self) -> syn::Expr {
+        syn::parse_quote!(native_runtime::read_slice())
+    }
+
+    fn commit(&self, value: &syn::Expr) -> syn::Expr {
+        syn::parse_quote!(native_runtime::commit(&#value))
+    }
+
+    fn commit_slice(&self, value: &syn::Expr) -> syn::Expr {
+        syn::parse_quote!(native_runtime::commit_slice(&#value[..]))
+    }
+
+    fn write_stdout(&self, value: &syn::Expr) -> syn::Expr {
+        syn::parse_quote!(native_runtime::write_stdout(&#value[..]))
+    }
+
+    fn write_stderr(&self, value: &syn::Expr) -> syn::Expr {
+        syn::parse_quote!(native_runtime::write_stderr(&#value[..]))
+    }
+
+    fn import(&self) -> Vec<syn::Item> {
+        vec![syn::Item::Macro(
+            syn::parse_str("native_runtime::entrypoint!(main);").unwrap(),
+        )]
+    }
+
+    fn generate_host_env(&self, inputs: &[InputWrite]) -> String {
+        write_inputs(
+            inputs,
+            |value| format!("native_runtime::write(&{});\n", value),
+            |bytes| format!("native_runtime::write_slice(&{}[..]);\n", bytes),
+        )
+    }
+
+    fn generate_output_decoding(&self, output_types: &[String]) -> String {
+        let mut decoding_code = String::new();
+        for (index, output_type) in output_types.iter().enumerate() {
+            decoding_code.push_str(&format!(
+                "let output{}: {} = native_runtime::decode(&journal[{}]);\n",
+                index + 1,
+                output_type,
+                index
+            ));
+        }
+        decoding_code
+    }
+
+    fn generate_raw_output_decoding(&self) -> String {
+        String::from("let output1: Vec<u8> = journal[0].clone();\n")
+    }
+
+    fn get_host_template(&self) -> String {
+        String::from(include_str!("../host_templates/native.rs"))
+    }
+
+    fn get_file_copy_destination(&self) -> String {
+        in_project(&self.root, &self.layout.host_src)
+    }
+
+    fn get_host_cargo_toml_path(&self) -> String {
+        in_project(&self.root, &self.layout.host_manifest)
+    }
+
+    fn get_guest_cargo_toml_path(&self) -> String {
+        in_project(&self.root, &self.layout.guest_manifest)
+    }
+
+    fn get_available_patches(&self) -> HashMap<String, String> {
+        // the baseline runs the unmodified crates.io dependencies
+        HashMap::new()
+    }
+
+    fn get_guest_output_dir(&self) -> String {
+        in_project(&self.root, &self.layout.guest_src)
     }
 
     fn get_host_output_dir(&self) -> String {
-        String::from("../risc0_benchmarks/test_project/host/src")
+        in_project(&self.root, &self.layout.host_src)
     }
 
     fn get_workspace_cargo_toml_path(&self) -> String {
-        String::from("../risc0_benchmarks/test_project/Cargo.toml")
+        in_project(&self.root, &self.layout.workspace_manifest)
     }
 }
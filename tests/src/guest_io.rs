@@ -0,0 +1,1320 @@
+//! The `env::read()` and `env::commit()` calls of a test template.
+//!
+//! The guest may call them anywhere: in nested blocks, loops and the helper functions of the
+//! template. The host has to write the inputs in the order the guest reads them and decode the
+//! outputs in the order it commits them, so the calls are collected in execution order by walking
+//! `main` and following its calls into the functions of the template.
+//!
+//! | Template call           | Guest                                             |
+//! |-------------------------|---------------------------------------------------|
+//! | `env::read()`           | a private input, deserialized                     |
+//! | `env::read_slice()`     | a private input as raw bytes                      |
+//! | `env::read_public()`    | an input that is committed where it is read       |
+//! | `env::commit(&v)`       | a public output, serialized                       |
+//! | `env::commit_slice(&b)` | a public output as raw bytes, the only one        |
+//! | `env::write_stdout(&b)` | bytes written to the standard output of the guest |
+//! | `env::write_stderr(&b)` | bytes written to the standard error of the guest  |
+//!
+//! A zkVM without an equivalent for some of them, see [`CodeEnv::unsupported`], rejects the
+//! templates that call them.
+
+use crate::codegen::{has_host_annotation, Template};
+use crate::diagnostics::CodegenError;
+use crate::env_adapters::{CodeEnv, InputValue, InputWrite};
+use proc_macro2::Span;
+use quote::ToTokens;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+use syn::visit_mut::{self, VisitMut};
+use syn::{
+    Attribute, Expr, ExprCall, ExprClosure, ExprForLoop, ExprIf, ExprLoop, ExprMatch, ExprWhile,
+    Fields, FnArg, Item, ItemFn, Lit, LitInt, LitStr, Local, Member, Pat, ReturnType, Stmt, Type,
+};
+
+/// A value the host generates for one `env::read()`.
+pub(crate) struct HostInput {
+    /// The host variable holding the value, e.g. `input1`
+    pub name: String,
+    /// The host function call generating it, e.g. `other_input(7)`
+    pub generator: String,
+    /// For a read in a loop, how often it runs. The variable then holds one value per run.
+    pub count: Option<usize>,
+}
+
+impl HostInput {
+    /// The statements binding the value on the host.
+    pub fn assignment(&self) -> String {
+        match self.count {
+            None => format!("let {} = {};", self.name, self.generator),
+            Some(count) => format!(
+                "let {name}: Vec<_> = {generator}.into_iter().collect();\n\
+                 assert_eq!({name}.len(), {count}, {message:?});",
+                name = self.name,
+                generator = self.generator,
+                count = count,
+                message = format!(
+                    "`{}` must generate {} values, one per read",
+                    self.generator, count
+                ),
+            ),
+        }
+    }
+}
+
+/// The inputs and outputs of a guest, in the order it reads and commits them.
+pub(crate) struct GuestIo {
+    pub inputs: Vec<HostInput>,
+    /// How the host writes the inputs
+    pub writes: Vec<InputWrite>,
+    /// The types of the committed values
+    pub output_types: Vec<String>,
+    /// The only output is committed as raw bytes
+    pub raw_output: bool,
+}
+
+impl GuestIo {
+    pub fn collect(
+        template: &Template,
+        main_function: &ItemFn,
+        env: &dyn CodeEnv,
+    ) -> Result<GuestIo, CodegenError> {
+        let mut finder = SiteFinder {
+            template,
+            env,
+            sites: Vec::new(),
+            io_functions: HashSet::new(),
+            calls: HashMap::new(),
+            function: None,
+            error: None,
+        };
+        finder.visit_file(&template.syntax_tree);
+        if let Some(err) = finder.error {
+            return Err(err);
+        }
+
+        let io_functions = finder.reaching_io();
+        let mut collector = Collector::new(template, io_functions);
+        collector.walk_function(main_function);
+        if let Some(err) = collector.error {
+            return Err(err);
+        }
+
+        // the host only knows when a call runs if it is reached from `main`
+        if let Some(site) = finder
+            .sites
+            .iter()
+            .find(|site| !collector.reached.contains(&position(site.span)))
+        {
+            return Err(template
+                .error(
+                    site.span,
+                    format!("cannot tell when this `env::{}` runs", site.name),
+                )
+                .with_help(
+                    "call it from `main`, or from a function of the template that `main` calls",
+                ));
+        }
+
+        let mut inputs = Vec::new();
+        let mut writes = Vec::new();
+        let mut last_scope = None;
+        for read in collector.reads {
+            let name = format!("input{}", inputs.len() + 1);
+            let value = InputValue {
+                name: name.clone(),
+                raw: read.raw,
+            };
+            let count = read.generator.count.as_ref().map(|(count, _)| *count);
+            match (read.scope, &read.generator.count) {
+                (None, None) => writes.push(InputWrite::Once(value)),
+                (None, Some((_, span))) => {
+                    return Err(template
+                        .error(*span, "`count` on a read that runs once")
+                        .with_help("`count` is for reads in a loop, condition or closure"));
+                }
+                (Some(_), None) => {
+                    return Err(template
+                        .error(read.span, "cannot tell how often this `env::read()` runs")
+                        .with_help(
+                            "declare it with `#[input(gen = \"...\", count = N)]` on the statement",
+                        ));
+                }
+                (Some(scope), Some((count, span))) => match writes.last_mut() {
+                    Some(InputWrite::Repeated {
+                        count: group_count,
+                        values,
+                    }) if last_scope == Some(scope) => {
+                        if group_count != count {
+                            return Err(template
+                                .error(
+                                    *span,
+                                    format!(
+                                        "reads in the same loop run {} and {} times",
+                                        group_count, count
+                                    ),
+                                )
+                                .with_help("reads in one loop must declare the same `count`"));
+                        }
+                        values.push(value);
+                    }
+                    _ => writes.push(InputWrite::Repeated {
+                        count: *count,
+                        values: vec![value],
+                    }),
+                },
+            }
+            last_scope = read.scope;
+            inputs.push(HostInput {
+                name,
+                generator: read.generator.call,
+                count,
+            });
+        }
+
+        if let Some(span) = collector.raw_commit {
+            if collector.output_types.len() > 1 {
+                return Err(template
+                    .error(span, "`env::commit_slice` is not the only commit")
+                    .with_help(
+                        "the host cannot tell raw bytes from other outputs, \
+                         commit them with `env::commit` instead",
+                    ));
+            }
+        }
+
+        Ok(GuestIo {
+            inputs,
+            writes,
+            output_types: collector.output_types,
+            raw_output: collector.raw_commit.is_some(),
+        })
+    }
+}
+
+/// Replaces every `env::` call of the guest with the calls of a zkVM, and strips the `#[input]`
+/// attributes.
+pub(crate) struct GuestRewriter<'a> {
+    pub env: &'a dyn CodeEnv,
+}
+
+impl VisitMut for GuestRewriter<'_> {
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        visit_mut::visit_expr_mut(self, expr);
+        let Expr::Call(call) = expr else {
+            return;
+        };
+        let Some(function) = env_function(call) else {
+            return;
+        };
+        let env = self.env;
+        let argument = || commit_operand(&call.args[0]);
+        let mut replacement = match function.ident.to_string().as_str() {
+            "read" => env.read(),
+            "read_slice" => env.read_slice(),
+            "read_public" => env.read_public(),
+            "commit" => env.commit(&argument()),
+            "commit_slice" => env.commit_slice(&argument()),
+            "write_stdout" => env.write_stdout(&argument()),
+            "write_stderr" => env.write_stderr(&argument()),
+            _ => return,
+        };
+        // keep an explicit type, e.g. `env::read::<u32>()`
+        if !function.arguments.is_empty() {
+            if let Expr::Call(ExprCall { func, .. }) = &mut replacement {
+                if let Expr::Path(path) = &mut **func {
+                    path.path.segments.last_mut().unwrap().arguments = function.arguments.clone();
+                }
+            }
+        }
+        *expr = replacement;
+    }
+
+    fn visit_local_mut(&mut self, local: &mut Local) {
+        local.attrs.retain(|attr| !attr.path().is_ident("input"));
+        visit_mut::visit_local_mut(self, local);
+    }
+}
+
+/// The value passed by reference to `env::commit(arg)` and the other writes, without a leading
+/// `&`. It is parenthesized unless it can be borrowed as it is.
+fn commit_operand(arg: &Expr) -> Expr {
+    let value = match arg {
+        Expr::Reference(reference) => (*reference.expr).clone(),
+        arg => arg.clone(),
+    };
+    match value {
+        Expr::Array(_)
+        | Expr::Call(_)
+        | Expr::Field(_)
+        | Expr::Index(_)
+        | Expr::Lit(_)
+        | Expr::Macro(_)
+        | Expr::MethodCall(_)
+        | Expr::Paren(_)
+        | Expr::Path(_)
+        | Expr::Tuple(_) => value,
+        value => syn::parse_quote!((#value)),
+    }
+}
+
+/// A call to an `env::` function somewhere in the template.
+struct Site {
+    name: String,
+    span: Span,
+}
+
+/// Finds and checks every `env::` call outside the `#[host]` functions.
+struct SiteFinder<'a> {
+    template: &'a Template,
+    env: &'a dyn CodeEnv,
+    sites: Vec<Site>,
+    /// Functions of the template that read or commit, directly
+    io_functions: HashSet<String>,
+    /// The functions each top-level function calls by name
+    calls: HashMap<String, HashSet<String>>,
+    /// The top-level function being visited
+    function: Option<String>,
+    error: Option<CodegenError>,
+}
+
+impl SiteFinder<'_> {
+    /// The functions that read or commit, directly or through any function they call.
+    fn reaching_io(&self) -> HashSet<String> {
+        let mut reaching = self.io_functions.clone();
+        loop {
+            let callers: Vec<&String> = self
+                .calls
+                .iter()
+                .filter(|(caller, callees)| {
+                    !reaching.contains(*caller) && !callees.is_disjoint(&reaching)
+                })
+                .map(|(caller, _)| caller)
+                .collect();
+            if callers.is_empty() {
+                return reaching;
+            }
+            reaching.extend(callers.into_iter().cloned());
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for SiteFinder<'_> {
+    fn visit_item(&mut self, item: &'ast Item) {
+        match item {
+            Item::Fn(func) if has_host_annotation(&func.attrs) => {}
+            Item::Fn(func) if self.function.is_none() => {
+                self.function = Some(func.sig.ident.to_string());
+                visit::visit_item(self, item);
+                self.function = None;
+            }
+            item => visit::visit_item(self, item),
+        }
+    }
+
+    fn visit_expr_call(&mut self, call: &'ast ExprCall) {
+        visit::visit_expr_call(self, call);
+        if self.error.is_some() {
+            return;
+        }
+        let Some(function) = env_function(call) else {
+            if let (Some(caller), Expr::Path(path)) = (&self.function, &*call.func) {
+                if let Some(callee) = path.path.get_ident() {
+                    let calls = self.calls.entry(caller.clone()).or_default();
+                    calls.insert(callee.to_string());
+                }
+            }
+            return;
+        };
+        let name = function.ident.to_string();
+        let help = "available: `read`, `read_slice`, `read_public`, `commit`, `commit_slice`, \
+                    `write_stdout` and `write_stderr`";
+        let expected_args = match name.as_str() {
+            "read" | "read_slice" | "read_public" => 0,
+            "commit" | "commit_slice" | "write_stdout" | "write_stderr" => 1,
+            name => {
+                self.error = Some(
+                    self.template
+                        .error(
+                            function.ident.span(),
+                            format!("unknown environment function `env::{}`", name),
+                        )
+                        .with_help(help),
+                );
+                return;
+            }
+        };
+        if let Some((_, reason)) = self
+            .env
+            .unsupported()
+            .iter()
+            .find(|(unsupported, _)| *unsupported == name)
+        {
+            self.error = Some(
+                self.template
+                    .error(
+                        function.ident.span(),
+                        format!("`env::{}` is not supported on {}", name, self.env.name()),
+                    )
+                    .with_help(*reason),
+            );
+            return;
+        }
+        if call.args.len() != expected_args {
+            self.error = Some(
+                self.template
+                    .error(
+                        call.span(),
+                        format!(
+                            "`env::{}` takes {} argument{}",
+                            name,
+                            expected_args,
+                            if expected_args == 1 { "" } else { "s" }
+                        ),
+                    )
+                    .with_help(help),
+            );
+            return;
+        }
+        if let Some(function) = &self.function {
+            self.io_functions.insert(function.clone());
+        }
+        self.sites.push(Site {
+            name: format!("{}()", name),
+            span: call.span(),
+        });
+    }
+}
+
+/// A read reached from `main`.
+struct Read {
+    generator: InputGenerator,
+    /// The innermost loop, conditional branch or closure the read runs in
+    scope: Option<usize>,
+    /// Read as raw bytes with `env::read_slice()`
+    raw: bool,
+    span: Span,
+}
+
+/// Walks the guest in execution order, from `main` into the functions it calls.
+struct Collector<'ast> {
+    template: &'ast Template,
+    functions: HashMap<String, &'ast ItemFn>,
+    host_functions: HashMap<String, &'ast ItemFn>,
+    structs: HashMap<String, &'ast Fields>,
+    io_functions: HashSet<String>,
+    /// Functions being walked, innermost last
+    call_stack: Vec<String>,
+    /// Statements being walked, innermost last
+    statements: Vec<&'ast Stmt>,
+    /// Loops, conditional branches and closures being walked, innermost last
+    scopes: Vec<usize>,
+    scope_count: usize,
+    /// Variables of the function being walked, with their types if known
+    locals: HashMap<String, Option<Type>>,
+    /// The read that takes its generator from an `#[input]` statement or a comment line
+    attribute_reads: HashMap<(usize, usize), (usize, usize)>,
+    comment_reads: HashMap<usize, (usize, usize)>,
+    reads: Vec<Read>,
+    output_types: Vec<String>,
+    /// The `env::commit_slice` reached, if any
+    raw_commit: Option<Span>,
+    /// Positions of the reads and commits reached
+    reached: HashSet<(usize, usize)>,
+    error: Option<CodegenError>,
+}
+
+impl<'ast> Collector<'ast> {
+    fn new(template: &'ast Template, io_functions: HashSet<String>) -> Self {
+        let mut functions = HashMap::new();
+        let mut host_functions = HashMap::new();
+        let mut structs = HashMap::new();
+        for item in &template.syntax_tree.items {
+            match item {
+                Item::Fn(func) if has_host_annotation(&func.attrs) => {
+                    host_functions.insert(func.sig.ident.to_string(), func);
+                }
+                Item::Fn(func) => {
+                    functions.insert(func.sig.ident.to_string(), func);
+                }
+                Item::Struct(item) => {
+                    structs.insert(item.ident.to_string(), &item.fields);
+                }
+                _ => {}
+            }
+        }
+        Collector {
+            template,
+            functions,
+            host_functions,
+            structs,
+            io_functions,
+            call_stack: Vec::new(),
+            statements: Vec::new(),
+            scopes: Vec::new(),
+            scope_count: 0,
+            locals: HashMap::new(),
+            attribute_reads: HashMap::new(),
+            comment_reads: HashMap::new(),
+            reads: Vec::new(),
+            output_types: Vec::new(),
+            raw_commit: None,
+            reached: HashSet::new(),
+            error: None,
+        }
+    }
+
+    fn walk_function(&mut self, func: &'ast ItemFn) {
+        let name = func.sig.ident.to_string();
+        if self.call_stack.contains(&name) {
+            self.error = Some(
+                self.template
+                    .error(
+                        func.sig.ident.span(),
+                        format!("`{}` reads or commits and calls itself", name),
+                    )
+                    .with_help("the host cannot tell how often a recursive function runs"),
+            );
+            return;
+        }
+
+        let caller_locals = std::mem::take(&mut self.locals);
+        for input in &func.sig.inputs {
+            if let FnArg::Typed(arg) = input {
+                if let Pat::Ident(pat) = &*arg.pat {
+                    self.locals
+                        .insert(pat.ident.to_string(), Some((*arg.ty).clone()));
+                }
+            }
+        }
+        self.call_stack.push(name);
+        self.visit_block(&func.block);
+        self.call_stack.pop();
+        self.locals = caller_locals;
+    }
+
+    /// Walk code that may run any number of times.
+    fn in_scope(&mut self, walk: impl FnOnce(&mut Self)) {
+        self.scope_count += 1;
+        self.scopes.push(self.scope_count);
+        walk(self);
+        self.scopes.pop();
+    }
+
+    fn read(&mut self, call: &'ast ExprCall, function: &str) {
+        let site = position(call.span());
+        self.reached.insert(site);
+        if self.scopes.len() > 1 {
+            self.error = Some(
+                self.template
+                    .error(
+                        call.span(),
+                        format!("`env::{}()` in a nested loop", function),
+                    )
+                    .with_help("read in a single loop, or read a `Vec` once before the loops"),
+            );
+            return;
+        }
+        let generator = match self.generator(call, site) {
+            Ok(generator) => generator,
+            Err(err) => {
+                self.error = Some(err);
+                return;
+            }
+        };
+        if function == "read_public" {
+            // the value is committed where it is read, with the type it is declared with
+            if !self.check_committed_once(call, function) {
+                return;
+            }
+            let ty = match self.statements.last() {
+                Some(Stmt::Local(local)) => match &local.pat {
+                    Pat::Type(pat_type) => Some(&*pat_type.ty),
+                    _ => None,
+                },
+                _ => None,
+            };
+            let Some(ty) = ty else {
+                self.error = Some(
+                    self.template
+                        .error(call.span(), "cannot infer the type of the public input")
+                        .with_help(
+                            "declare it with a type, e.g. `let n: u32 = env::read_public();`",
+                        ),
+                );
+                return;
+            };
+            self.output_types.push(ty.to_token_stream().to_string());
+        }
+        self.reads.push(Read {
+            generator,
+            scope: self.scopes.last().copied(),
+            raw: function == "read_slice",
+            span: call.span(),
+        });
+    }
+
+    /// Commits have to run exactly once, in a fixed order, for the host to decode them.
+    fn check_committed_once(&mut self, call: &ExprCall, function: &str) -> bool {
+        if self.scopes.is_empty() {
+            return true;
+        }
+        self.error = Some(
+            self.template
+                .error(
+                    call.span(),
+                    format!(
+                        "`env::{}` in a loop, conditional branch or closure",
+                        function
+                    ),
+                )
+                .with_help(
+                    "the host decodes the outputs in a fixed order, \
+                     collect the values and commit them once",
+                ),
+        );
+        false
+    }
+
+    /// The generator of a read, from an `#[input]` attribute or a comment on its statement.
+    fn generator(
+        &mut self,
+        call: &ExprCall,
+        site: (usize, usize),
+    ) -> Result<InputGenerator, CodegenError> {
+        let template = self.template;
+        let stmt = *self.statements.last().unwrap();
+        let attribute = match stmt {
+            Stmt::Local(local) => local
+                .attrs
+                .iter()
+                .find(|attr| attr.path().is_ident("input")),
+            _ => None,
+        };
+        let line = end_line(stmt);
+        let comment = comment_generator(template, line)?;
+
+        let generator = match (attribute, comment) {
+            (Some(attr), comment) => {
+                let claimed = self.attribute_reads.entry(position(stmt.span()));
+                if *claimed.or_insert(site) != site {
+                    return Err(template
+                        .error(call.span(), "`#[input]` is used by two reads")
+                        .with_help("read each input in its own `let` statement"));
+                }
+                let generator = parse_input_attribute(attr, template)?;
+                if let Some(comment) = comment {
+                    if comment.call != generator.call {
+                        return Err(template
+                            .error(
+                                attr.span(),
+                                "conflicting input generators in `#[input]` and comment",
+                            )
+                            .with_help(format!(
+                                "the comment names `{}`, remove one of them",
+                                comment.call
+                            )));
+                    }
+                }
+                generator
+            }
+            (None, Some(comment)) => {
+                // one comment cannot tell two reads ending on the same line apart
+                if *self.comment_reads.entry(line).or_insert(site) != site {
+                    return Err(template
+                        .error(call.span(), "input generator comment is used by two reads")
+                        .with_help(
+                            "put each read on its own line, or use `#[input(gen = \"...\")]`",
+                        ));
+                }
+                comment
+            }
+            (None, None) => {
+                return Err(template
+                    .error(call.span(), "missing input generator for `env::read()`")
+                    .with_help(
+                        "name the host function that generates the input with \
+                         `#[input(gen = \"input()\")]` or a comment, e.g. `// #input()`",
+                    ))
+            }
+        };
+
+        let Some(func) = self.host_functions.get(&generator.name) else {
+            return Err(generator
+                .error(
+                    template,
+                    format!("no `#[host] fn {}` in the template", generator.name),
+                )
+                .with_help("input generators are `#[host]` functions of the template"));
+        };
+        let params = func.sig.inputs.len();
+        if params != generator.args {
+            return Err(generator.error(
+                template,
+                format!(
+                    "`{}` takes {} argument{}, but {} {} given",
+                    generator.name,
+                    params,
+                    if params == 1 { "" } else { "s" },
+                    generator.args,
+                    if generator.args == 1 { "was" } else { "were" }
+                ),
+            ));
+        }
+        Ok(generator)
+    }
+
+    fn commit(&mut self, call: &'ast ExprCall, function: &str) {
+        self.reached.insert(position(call.span()));
+        if !self.check_committed_once(call, function) {
+            return;
+        }
+        if function == "commit_slice" {
+            self.raw_commit = Some(call.span());
+            self.output_types.push(String::from("Vec<u8>"));
+            return;
+        }
+        let arg = &call.args[0];
+        match self.infer_type(arg) {
+            Some(ty) => self.output_types.push(ty.to_token_stream().to_string()),
+            None => {
+                self.error = Some(
+                    self.template
+                        .error(arg.span(), "cannot infer the type of the committed value")
+                        .with_help(
+                            "declare the variable with a type annotation, \
+                             e.g. `let answer: u32 = ...`",
+                        ),
+                )
+            }
+        }
+    }
+
+    /// Infer the type of a committed value.
+    ///
+    /// A variable takes the type it was declared with (`let x: T = ...`). Without an annotation
+    /// the type is taken from its initializer when that is a call to a function of the template,
+    /// a literal, a struct expression or another variable. Tuples, fields of the structs of the
+    /// template and indexing are followed from there.
+    fn infer_type(&self, expr: &Expr) -> Option<Type> {
+        match expr {
+            Expr::Reference(reference) => self.infer_type(&reference.expr),
+            Expr::Paren(paren) => self.infer_type(&paren.expr),
+            Expr::Cast(cast) => Some((*cast.ty).clone()),
+            Expr::Struct(item) => {
+                let path = &item.path;
+                Some(syn::parse_quote!(#path))
+            }
+            Expr::Path(path) => {
+                let ident = path.path.get_ident()?;
+                self.locals.get(&ident.to_string()).cloned().flatten()
+            }
+            Expr::Tuple(tuple) => {
+                let elems = tuple
+                    .elems
+                    .iter()
+                    .map(|elem| self.infer_type(elem))
+                    .collect::<Option<Vec<Type>>>()?;
+                Some(syn::parse_quote!((#(#elems,)*)))
+            }
+            Expr::Field(field) => match (self.infer_type(&field.base)?, &field.member) {
+                (Type::Tuple(tuple), Member::Unnamed(index)) => {
+                    tuple.elems.into_iter().nth(index.index as usize)
+                }
+                (Type::Path(path), member) => {
+                    let name = path.path.segments.last()?.ident.to_string();
+                    let field = match (self.structs.get(&name)?, member) {
+                        (Fields::Named(fields), Member::Named(ident)) => fields
+                            .named
+                            .iter()
+                            .find(|field| field.ident.as_ref() == Some(ident)),
+                        (Fields::Unnamed(fields), Member::Unnamed(index)) => {
+                            fields.unnamed.iter().nth(index.index as usize)
+                        }
+                        _ => None,
+                    }?;
+                    Some(field.ty.clone())
+                }
+                _ => None,
+            },
+            Expr::Index(index) => match self.infer_type(&index.expr)? {
+                Type::Array(array) => Some(*array.elem),
+                Type::Slice(slice) => Some(*slice.elem),
+                Type::Path(path) => {
+                    let segment = path.path.segments.last()?;
+                    if segment.ident != "Vec" {
+                        return None;
+                    }
+                    match &segment.arguments {
+                        syn::PathArguments::AngleBracketed(args) => match args.args.first()? {
+                            syn::GenericArgument::Type(ty) => Some(ty.clone()),
+                            _ => None,
+                        },
+                        _ => None,
+                    }
+                }
+                _ => None,
+            },
+            Expr::Call(call) => {
+                let Expr::Path(path) = &*call.func else {
+                    return None;
+                };
+                let name = path.path.get_ident()?.to_string();
+                let func = self
+                    .functions
+                    .get(&name)
+                    .or_else(|| self.host_functions.get(&name))?;
+                match &func.sig.output {
+                    ReturnType::Default => Some(syn::parse_quote!(())),
+                    ReturnType::Type(_, ty) if matches!(**ty, Type::ImplTrait(_)) => None,
+                    ReturnType::Type(_, ty) => Some((**ty).clone()),
+                }
+            }
+            Expr::Lit(lit) => match &lit.lit {
+                // unsuffixed literals get the types Rust defaults them to
+                Lit::Int(int) if int.suffix().is_empty() => Some(syn::parse_quote!(i32)),
+                Lit::Int(int) => syn::parse_str(int.suffix()).ok(),
+                Lit::Float(float) if float.suffix().is_empty() => Some(syn::parse_quote!(f64)),
+                Lit::Float(float) => syn::parse_str(float.suffix()).ok(),
+                Lit::Bool(_) => Some(syn::parse_quote!(bool)),
+                Lit::Char(_) => Some(syn::parse_quote!(char)),
+                Lit::Str(_) => Some(syn::parse_quote!(String)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for Collector<'ast> {
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+        if self.error.is_some() {
+            return;
+        }
+        self.statements.push(stmt);
+        visit::visit_stmt(self, stmt);
+        self.statements.pop();
+
+        if let Stmt::Local(local) = stmt {
+            if let Some(attr) = local
+                .attrs
+                .iter()
+                .find(|attr| attr.path().is_ident("input"))
+            {
+                if self.error.is_none()
+                    && !self.attribute_reads.contains_key(&position(stmt.span()))
+                {
+                    self.error = Some(
+                        self.template
+                            .error(
+                                attr.span(),
+                                "`#[input]` on a statement without `env::read()`",
+                            )
+                            .with_help("`#[input]` binds the value of `let ... = env::read();`"),
+                    );
+                }
+            }
+        }
+    }
+
+    fn visit_local(&mut self, local: &'ast Local) {
+        visit::visit_local(self, local);
+        let (pat, ty) = match &local.pat {
+            Pat::Type(pat_type) => (&*pat_type.pat, Some((*pat_type.ty).clone())),
+            pat => (
+                pat,
+                local
+                    .init
+                    .as_ref()
+                    .and_then(|init| self.infer_type(&init.expr)),
+            ),
+        };
+        // a later `let` with the same name shadows the earlier one
+        if let Pat::Ident(pat_ident) = pat {
+            self.locals.insert(pat_ident.ident.to_string(), ty);
+        }
+    }
+
+    // items nested in a function only run when they are called
+    fn visit_item(&mut self, _item: &'ast Item) {}
+
+    fn visit_expr_call(&mut self, call: &'ast ExprCall) {
+        visit::visit_expr_call(self, call);
+        if self.error.is_some() {
+            return;
+        }
+        if let Some(function) = env_function(call) {
+            let function = function.ident.to_string();
+            match function.as_str() {
+                "read" | "read_slice" | "read_public" => self.read(call, &function),
+                "commit" | "commit_slice" => self.commit(call, &function),
+                _ => {
+                    self.reached.insert(position(call.span()));
+                }
+            }
+        } else if let Expr::Path(path) = &*call.func {
+            let Some(ident) = path.path.get_ident() else {
+                return;
+            };
+            if !self.io_functions.contains(&ident.to_string()) {
+                return;
+            }
+            if let Some(func) = self.functions.get(&ident.to_string()).copied() {
+                self.walk_function(func);
+            }
+        }
+    }
+
+    fn visit_expr_for_loop(&mut self, expr: &'ast ExprForLoop) {
+        self.visit_expr(&expr.expr);
+        self.in_scope(|this| this.visit_block(&expr.body));
+    }
+
+    fn visit_expr_while(&mut self, expr: &'ast ExprWhile) {
+        self.in_scope(|this| {
+            this.visit_expr(&expr.cond);
+            this.visit_block(&expr.body);
+        });
+    }
+
+    fn visit_expr_loop(&mut self, expr: &'ast ExprLoop) {
+        self.in_scope(|this| this.visit_block(&expr.body));
+    }
+
+    fn visit_expr_if(&mut self, expr: &'ast ExprIf) {
+        self.visit_expr(&expr.cond);
+        self.in_scope(|this| this.visit_block(&expr.then_branch));
+        if let Some((_, else_branch)) = &expr.else_branch {
+            self.in_scope(|this| this.visit_expr(else_branch));
+        }
+    }
+
+    fn visit_expr_match(&mut self, expr: &'ast ExprMatch) {
+        self.visit_expr(&expr.expr);
+        for arm in &expr.arms {
+            self.in_scope(|this| visit::visit_arm(this, arm));
+        }
+    }
+
+    fn visit_expr_closure(&mut self, expr: &'ast ExprClosure) {
+        self.in_scope(|this| visit::visit_expr_closure(this, expr));
+    }
+}
+
+/// A host function call that generates the value of one `env::read()`.
+struct InputGenerator {
+    name: String,
+    args: usize,
+    /// The call as it is pasted into the host code, e.g. `other_input (7)`
+    call: String,
+    /// How often the read runs, with the span of the declaration
+    count: Option<(usize, Span)>,
+    origin: GeneratorOrigin,
+}
+
+/// Where an input generator was declared, to point errors at it.
+enum GeneratorOrigin {
+    Attribute(Span),
+    /// 1-based line and the byte range of the call within the line
+    Comment(usize, Range<usize>),
+}
+
+impl InputGenerator {
+    /// Parse `name` or `name(args)`.
+    fn parse(
+        text: &str,
+        origin: GeneratorOrigin,
+        template: &Template,
+    ) -> Result<Self, CodegenError> {
+        let malformed = |origin: &GeneratorOrigin| {
+            let message = format!("malformed input generator `{}`", text);
+            let err = match origin {
+                GeneratorOrigin::Attribute(span) => template.error(*span, message),
+                GeneratorOrigin::Comment(line, range) => {
+                    template.error_in_line(*line, range.clone(), message)
+                }
+            };
+            err.with_help("expected a function name with optional arguments, e.g. `input(10)`")
+        };
+        let (name, args) = match syn::parse_str::<Expr>(text) {
+            Ok(Expr::Path(path)) => (path.path.get_ident().cloned(), None),
+            Ok(Expr::Call(call)) => match &*call.func {
+                Expr::Path(path) => (path.path.get_ident().cloned(), Some(call.args)),
+                _ => (None, None),
+            },
+            _ => (None, None),
+        };
+        let name = name.ok_or_else(|| malformed(&origin))?.to_string();
+        let args = args.unwrap_or_default();
+        let call = format!("{}({})", name, args.to_token_stream());
+        Ok(InputGenerator {
+            name,
+            args: args.len(),
+            call,
+            count: None,
+            origin,
+        })
+    }
+
+    fn error(&self, template: &Template, message: impl Into<String>) -> CodegenError {
+        match &self.origin {
+            GeneratorOrigin::Attribute(span) => template.error(*span, message),
+            GeneratorOrigin::Comment(line, range) => {
+                template.error_in_line(*line, range.clone(), message)
+            }
+        }
+    }
+}
+
+/// Parse `#[input(gen = "...")]`, optionally with `count = N`.
+fn parse_input_attribute(
+    attr: &Attribute,
+    template: &Template,
+) -> Result<InputGenerator, CodegenError> {
+    let mut generator = None;
+    let mut count = None;
+    attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("gen") {
+            let value: LitStr = meta.value()?.parse()?;
+            generator = Some(value);
+            Ok(())
+        } else if meta.path.is_ident("count") {
+            let value: LitInt = meta.value()?.parse()?;
+            count = Some((value.base10_parse::<usize>()?, value.span()));
+            Ok(())
+        } else {
+            Err(meta.error("unknown `#[input]` argument"))
+        }
+    })
+    .map_err(|err| {
+        template
+            .error(err.span(), err.to_string())
+            .with_help("expected `#[input(gen = \"function(args)\")]`")
+    })?;
+    let value = generator.ok_or_else(|| {
+        template
+            .error(attr.span(), "`#[input]` does not name a generator")
+            .with_help("expected `#[input(gen = \"function(args)\")]`")
+    })?;
+    let mut generator = InputGenerator::parse(
+        &value.value(),
+        GeneratorOrigin::Attribute(value.span()),
+        template,
+    )?;
+    generator.count = count;
+    Ok(generator)
+}
+
+/// The generator named by a `// #function(args)` comment on a 1-based line, if any.
+fn comment_generator(
+    template: &Template,
+    line: usize,
+) -> Result<Option<InputGenerator>, CodegenError> {
+    let source_line = template.source.lines().nth(line - 1).unwrap_or("");
+    let Some(comment_start) = source_line.find("// #") else {
+        return Ok(None);
+    };
+    let start = comment_start + 4;
+    let end = start + source_line[start..].trim_end().len();
+    InputGenerator::parse(
+        &source_line[start..end],
+        GeneratorOrigin::Comment(line, start..end),
+        template,
+    )
+    .map(Some)
+}
+
+/// The last line of a statement, where a generator comment goes.
+fn end_line(stmt: &Stmt) -> usize {
+    match stmt {
+        Stmt::Local(local) => local.semi_token.span.start().line,
+        Stmt::Expr(_, Some(semi)) => semi.span.start().line,
+        stmt => stmt.span().end().line,
+    }
+}
+
+fn position(span: Span) -> (usize, usize) {
+    let start = span.start();
+    (start.line, start.column)
+}
+
+/// The function segment of an `env::<function>(...)` call
+fn env_function(call: &ExprCall) -> Option<&syn::PathSegment> {
+    match &*call.func {
+        Expr::Path(path) => {
+            let segments = &path.path.segments;
+            (segments.len() == 2 && segments[0].ident == "env").then(|| &segments[1])
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env_adapters::{Layout, NativeEnv, ValidaEnv};
+    use std::path::PathBuf;
+
+    fn layout() -> Layout {
+        Layout {
+            workspace_manifest: PathBuf::from("Cargo.toml"),
+            guest_manifest: PathBuf::from("guest/Cargo.toml"),
+            guest_src: PathBuf::from("guest/src"),
+            host_manifest: PathBuf::from("host/Cargo.toml"),
+            host_src: PathBuf::from("host/src"),
+        }
+    }
+
+    fn native_env() -> NativeEnv {
+        NativeEnv {
+            root: PathBuf::from("project"),
+            layout: layout(),
+        }
+    }
+
+    fn template(source: &str) -> Template {
+        Template {
+            path: PathBuf::from("template.rs"),
+            source: source.to_string(),
+            syntax_tree: syn::parse_file(source).unwrap(),
+        }
+    }
+
+    fn collect(source: &str) -> Result<GuestIo, CodegenError> {
+        collect_for(source, &native_env())
+    }
+
+    fn collect_for(source: &str, env: &dyn CodeEnv) -> Result<GuestIo, CodegenError> {
+        let template = template(source);
+        let main = template
+            .syntax_tree
+            .items
+            .iter()
+            .find_map(|item| match item {
+                Item::Fn(func) if func.sig.ident == "main" => Some(func),
+                _ => None,
+            })
+            .unwrap();
+        GuestIo::collect(&template, main, env)
+    }
+
+    fn error(source: &str) -> String {
+        match collect(source) {
+            Ok(_) => panic!("the template was accepted"),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    fn generators(io: &GuestIo) -> Vec<(&str, Option<usize>)> {
+        io.inputs
+            .iter()
+            .map(|input| (input.generator.as_str(), input.count))
+            .collect()
+    }
+
+    #[test]
+    fn repeats_a_read_in_a_loop_with_a_count() {
+        let io = collect(
+            r#"
+            #[host]
+            fn value() -> Vec<u32> { vec![1, 2, 3] }
+
+            fn main() {
+                let mut sum: u32 = 0;
+                for _ in 0..3 {
+                    #[input(gen = "value()", count = 3)]
+                    let x: u32 = env::read();
+                    sum += x;
+                }
+                env::commit(&sum);
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(generators(&io), [("value()", Some(3))]);
+        assert!(matches!(
+            io.writes.as_slice(),
+            [InputWrite::Repeated { count: 3, values }] if values.len() == 1
+        ));
+        assert_eq!(io.output_types, ["u32"]);
+    }
+
+    #[test]
+    fn rejects_a_read_in_a_nested_loop() {
+        let err = error(
+            r#"
+            #[host]
+            fn value() -> Vec<u32> { vec![1; 4] }
+
+            fn main() {
+                for _ in 0..2 {
+                    for _ in 0..2 {
+                        #[input(gen = "value()", count = 4)]
+                        let x: u32 = env::read();
+                    }
+                }
+            }
+            "#,
+        );
+        assert!(err.contains("`env::read()` in a nested loop"), "{}", err);
+    }
+
+    #[test]
+    fn reads_once_per_call_of_a_helper() {
+        let io = collect(
+            r#"
+            #[host]
+            fn value(n: u32) -> u32 { n }
+
+            fn helper() -> u32 {
+                let x: u32 = env::read(); // #value(7)
+                x
+            }
+
+            fn main() {
+                let a: u32 = helper();
+                let b: u32 = helper();
+                let sum: u32 = a + b;
+                env::commit(&sum);
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(generators(&io), [("value(7)", None), ("value(7)", None)]);
+        assert_eq!(io.writes.len(), 2);
+    }
+
+    #[test]
+    fn follows_calls_through_functions_without_io() {
+        let io = collect(
+            r#"
+            #[host]
+            fn value() -> u32 { 1 }
+
+            fn reader() -> u32 {
+                let x: u32 = env::read(); // #value()
+                x
+            }
+
+            fn helper() -> u32 {
+                reader() + 1
+            }
+
+            fn main() {
+                let answer: u32 = helper();
+                env::commit(&answer);
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(generators(&io), [("value()", None)]);
+        assert_eq!(io.output_types, ["u32"]);
+    }
+
+    #[test]
+    fn infers_the_type_of_a_committed_tuple_and_field() {
+        let io = collect(
+            r#"
+            struct Point { x: u64, y: i8 }
+
+            fn origin() -> Point { Point { x: 0, y: 0 } }
+
+            fn main() {
+                let point = origin();
+                let pair = (point.x, true);
+                env::commit(&pair);
+                env::commit(&point.y);
+                env::commit(&pair.0);
+            }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(io.output_types, ["(u64 , bool ,)", "i8", "u64"]);
+    }
+
+    #[test]
+    fn rejects_a_commit_in_a_loop() {
+        let err = error(
+            r#"
+            fn main() {
+                for i in 0..3u32 {
+                    env::commit(&i);
+                }
+            }
+            "#,
+        );
+        assert!(
+            err.contains("`env::commit` in a loop, conditional branch or closure"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn rejects_a_read_main_never_reaches() {
+        let err = error(
+            r#"
+            #[host]
+            fn value() -> u32 { 1 }
+
+            fn unused() {
+                let x: u32 = env::read(); // #value()
+            }
+
+            fn main() {}
+            "#,
+        );
+        assert!(
+            err.contains("cannot tell when this `env::read()` runs"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn rejects_a_function_the_zkvm_does_not_support() {
+        let source = r#"
+            fn main() {
+                env::write_stdout(b"hello");
+            }
+            "#;
+        assert!(collect(source).is_ok());
+        let env = ValidaEnv {
+            root: PathBuf::from("project"),
+            layout: layout(),
+        };
+        let err = match collect_for(source, &env) {
+            Ok(_) => panic!("the template was accepted"),
+            Err(err) => err.to_string(),
+        };
+        assert!(
+            err.contains("`env::write_stdout` is not supported on valida"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn rewrites_the_calls_for_a_zkvm() {
+        let env = native_env();
+        let mut main: ItemFn = syn::parse_quote! {
+            fn main() {
+                #[input(gen = "value()")]
+                let n: u32 = env::read::<u32>();
+                env::commit(&(n, n + 1));
+            }
+        };
+        GuestRewriter { env: &env }.visit_item_fn_mut(&mut main);
+        let expected: ItemFn = syn::parse_quote! {
+            fn main() {
+                let n: u32 = native_runtime::read::<u32>();
+                native_runtime::commit(&(n, n + 1));
+            }
+        };
+        assert_eq!(
+            main.to_token_stream().to_string(),
+            expected.to_token_stream().to_string()
+        );
+    }
+}
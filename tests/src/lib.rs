@@ -0,0 +1,8 @@
+pub mod codegen;
+pub mod config;
+pub mod diagnostics;
+pub mod env_adapters;
+mod guest_io;
+mod manifest;
+pub mod render;
+pub mod runner;
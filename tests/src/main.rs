@@ -1,34 +1,50 @@
-mod codegen;
-mod env_adapters;
-use codegen::CodeGenerator;
+use std::fs;
+use tests::config::Config;
+use tests::runner::{self, RunError, Vm};
 
 fn main() {
     // get template name from command line
     let args: Vec<String> = std::env::args().collect();
     let template_name = &args[1];
-    let template_path = format!("./test_templates/{}", template_name);
     // check if reset flag is set
     let mut reset_flag: bool = false;
     if args.len() > 2 {
         reset_flag = &args[2] == "true";
-    } 
+    }
 
-    let generator = CodeGenerator::new(Box::new(env_adapters::Risc0Env));
-    generator.generate_code(
-        &template_path,
-        "../risc0_benchmarks/test_project/methods/guest/src",
-        "../risc0_benchmarks/test_project/host/src",
-    );
+    if let Err(err) = generate_all(template_name, reset_flag) {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    }
+}
 
-    let sp1_generator = CodeGenerator::new(Box::new(env_adapters::Sp1Env));
-    sp1_generator.generate_code(
-        &template_path,
-        "../sp1_benchmarks/sp1_project/program/src",
-        "../sp1_benchmarks/sp1_project/script/src/bin",
-    );
+/// Generate the template for every zkVM, each in the workspace `zkbench run` uses for it,
+/// `<build dir>/<zkvm>/<test>`. The checked-in projects are only read. With `reset`, the
+/// workspaces are removed again once every zkVM generated, so only the check remains.
+fn generate_all(template_name: &str, reset: bool) -> Result<(), RunError> {
+    let config = Config::find().map_err(RunError::Codegen)?;
+    let template_path = config.templates.join(template_name);
+
+    let mut workspaces = Vec::new();
+    for vm in Vm::ALL {
+        let workspace = runner::create_workspace(&config.build, vm, template_name)?;
+        println!(
+            "==> Generating {} code for {} in {}",
+            vm.name(),
+            template_name,
+            workspace.display()
+        );
+        runner::generate(&config, vm, &template_path, &workspace)?;
+        workspaces.push(workspace);
+    }
 
-    if reset_flag {
-        generator.reset();
-        sp1_generator.reset();
+    if reset {
+        for workspace in workspaces {
+            fs::remove_dir_all(&workspace).map_err(|source| RunError::Io {
+                phase: "removing the workspace",
+                source,
+            })?;
+        }
     }
+    Ok(())
 }
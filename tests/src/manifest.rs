@@ -0,0 +1,368 @@
+//! Format-preserving edits of the zkVM project manifests.
+//!
+//! Codegen adds the dependencies of a template and the precompile patches of a zkVM to the
+//! Cargo.toml files of the project. The edits keep the comments, order and formatting of the rest
+//! of the file, and the change made to each manifest is printed as a diff so a run can be reviewed.
+
+use crate::codegen::write_atomically;
+use crate::diagnostics::CodegenError;
+use similar::TextDiff;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Item, Table, TableLike, TomlError, Value};
+
+pub(crate) struct Manifest {
+    path: PathBuf,
+    original: String,
+    document: DocumentMut,
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Manifest, CodegenError> {
+        let original =
+            fs::read_to_string(path).map_err(|err| CodegenError::io("read", path, err))?;
+        let document = original
+            .parse::<DocumentMut>()
+            .map_err(|err| toml_error(path, &original, err))?;
+        Ok(Manifest {
+            path: path.to_path_buf(),
+            original,
+            document,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Add a dependency, replacing any dependency of the same name. Returns the replaced
+    /// specification if it asks for another crate, source or an incompatible version.
+    pub fn add_dependency(
+        &mut self,
+        name: &str,
+        dependency: &Item,
+    ) -> Result<Option<String>, CodegenError> {
+        let dependencies = self
+            .document
+            .get_mut("dependencies")
+            .and_then(Item::as_table_like_mut)
+            .ok_or_else(|| {
+                CodegenError::new("missing `[dependencies]` table").in_file(&self.path)
+            })?;
+
+        let conflict = dependencies.get(name).and_then(|existing| {
+            Requirement::of(name, existing)
+                .conflicts_with(&Requirement::of(name, dependency))
+                .then(|| describe(name, existing))
+        });
+        replace(dependencies, name, dependency);
+        Ok(conflict)
+    }
+
+    /// Add `[patch.crates-io]` entries, given as TOML like `sha2 = { git = "..." }`.
+    pub fn add_patch(&mut self, patch: &str) -> Result<(), CodegenError> {
+        let patch: DocumentMut = patch.parse().map_err(|err: TomlError| {
+            let message = err.message().trim_end().replace('\n', ": ");
+            CodegenError::new(format!("invalid precompile patch: {}", message))
+                .in_file(&self.path)
+                .with_help(format!(
+                    "the patch comes from the zkVM adapter, fix it there:\n{}",
+                    patch.trim()
+                ))
+        })?;
+        let crates_io = self
+            .document
+            .entry("patch")
+            .or_insert_with(|| {
+                let mut table = Table::new();
+                table.set_implicit(true);
+                Item::Table(table)
+            })
+            .as_table_like_mut()
+            .and_then(|patches| {
+                patches
+                    .entry("crates-io")
+                    .or_insert_with(toml_edit::table)
+                    .as_table_like_mut()
+            })
+            .ok_or_else(|| {
+                CodegenError::new("`patch.crates-io` is not a table").in_file(&self.path)
+            })?;
+
+        for (name, item) in patch.iter() {
+            replace(crates_io, name, item);
+        }
+        Ok(())
+    }
+
+    /// Make the `path` dependencies that lead out of `project` absolute. Relative paths are
+    /// resolved from `original_dir`, where the manifest is in `project`, so the manifest keeps
+    /// working in a copy of the project made anywhere else.
+    pub fn rebase_paths(&mut self, original_dir: &Path, project: &Path) {
+        let Ok(project) = project.canonicalize() else {
+            return;
+        };
+        let document = self.document.as_table_mut();
+        let mut tables: Vec<&mut dyn TableLike> = Vec::new();
+        for (name, item) in document.iter_mut() {
+            match name.get() {
+                "dependencies" | "dev-dependencies" | "build-dependencies" => {
+                    tables.extend(item.as_table_like_mut());
+                }
+                "workspace" => tables.extend(
+                    item.get_mut("dependencies")
+                        .and_then(Item::as_table_like_mut),
+                ),
+                // one table per patched registry
+                "patch" => {
+                    if let Some(patches) = item.as_table_like_mut() {
+                        for (_, registry) in patches.iter_mut() {
+                            tables.extend(registry.as_table_like_mut());
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for table in tables {
+            for (_, dependency) in table.iter_mut() {
+                rebase_path(dependency, original_dir, &project);
+            }
+        }
+    }
+
+    /// Write the manifest back if it changed, and print the change as a unified diff.
+    pub fn save(self) -> Result<(), CodegenError> {
+        let updated = self.document.to_string();
+        if updated == self.original {
+            return Ok(());
+        }
+        write_atomically(&self.path, &updated)?;
+
+        let path = self.path.display().to_string();
+        print!(
+            "{}",
+            TextDiff::from_lines(&self.original, &updated)
+                .unified_diff()
+                .context_radius(2)
+                .header(&path, &path)
+        );
+        Ok(())
+    }
+}
+
+/// Make the `path` of a dependency that leads out of `project` absolute, as
+/// [`Manifest::rebase_paths`] does for the dependencies of a manifest.
+pub(crate) fn rebase_dependency(dependency: &mut Item, original_dir: &Path, project: &Path) {
+    if let Ok(project) = project.canonicalize() {
+        rebase_path(dependency, original_dir, &project);
+    }
+}
+
+fn rebase_path(dependency: &mut Item, original_dir: &Path, project: &Path) {
+    let Some(path) = dependency
+        .as_table_like_mut()
+        .and_then(|dependency| dependency.get_mut("path"))
+        .and_then(Item::as_value_mut)
+    else {
+        return;
+    };
+    let Some(resolved) = path
+        .as_str()
+        .and_then(|relative| original_dir.join(relative).canonicalize().ok())
+    else {
+        return;
+    };
+    if !resolved.starts_with(project) {
+        let decor = path.decor().clone();
+        *path = resolved.to_string_lossy().as_ref().into();
+        *path.decor_mut() = decor;
+    }
+}
+
+/// What a dependency specification resolves to, as far as telling whether two of them can be
+/// satisfied by the same crate goes.
+struct Requirement {
+    package: String,
+    source: Source,
+    version: Option<VersionRange>,
+}
+
+#[derive(PartialEq)]
+enum Source {
+    Registry,
+    Git {
+        url: String,
+        reference: Option<String>,
+    },
+    Path(String),
+    Workspace,
+}
+
+/// The versions a requirement accepts. Caret requirements, Cargo's default, are compared by the
+/// leftmost non-zero component they keep fixed, so `0.10` and `0.10.8` match but `0.9` does not.
+/// Any other requirement has to be written identically.
+#[derive(PartialEq)]
+enum VersionRange {
+    Caret { position: usize, value: u64 },
+    Exact(String),
+}
+
+impl Requirement {
+    fn of(name: &str, item: &Item) -> Requirement {
+        let Some(table) = item.as_table_like() else {
+            return Requirement {
+                package: name.to_string(),
+                source: Source::Registry,
+                version: item.as_str().map(VersionRange::parse),
+            };
+        };
+        let string = |key: &str| table.get(key).and_then(Item::as_str).map(str::to_string);
+
+        let source = if table
+            .get("workspace")
+            .and_then(Item::as_bool)
+            .unwrap_or(false)
+        {
+            Source::Workspace
+        } else if let Some(path) = string("path") {
+            Source::Path(path)
+        } else if let Some(url) = string("git") {
+            Source::Git {
+                url,
+                reference: string("rev")
+                    .or_else(|| string("tag"))
+                    .or_else(|| string("branch")),
+            }
+        } else {
+            Source::Registry
+        };
+        Requirement {
+            package: string("package").unwrap_or_else(|| name.to_string()),
+            source,
+            version: string("version").as_deref().map(VersionRange::parse),
+        }
+    }
+
+    /// A requirement without a version, like a path dependency, accepts the version of the other.
+    fn conflicts_with(&self, other: &Requirement) -> bool {
+        let versions_differ = match (&self.version, &other.version) {
+            (Some(version), Some(other)) => version != other,
+            _ => false,
+        };
+        self.package != other.package || self.source != other.source || versions_differ
+    }
+}
+
+impl VersionRange {
+    fn parse(requirement: &str) -> VersionRange {
+        let requirement = requirement.trim();
+        let components: Option<Vec<u64>> = requirement
+            .strip_prefix('^')
+            .unwrap_or(requirement)
+            .split('.')
+            .map(|component| component.parse().ok())
+            .collect();
+        match components {
+            Some(components) => {
+                let position = components
+                    .iter()
+                    .position(|component| *component != 0)
+                    .unwrap_or(components.len());
+                VersionRange::Caret {
+                    position,
+                    value: components.get(position).copied().unwrap_or(0),
+                }
+            }
+            None => VersionRange::Exact(requirement.to_string()),
+        }
+    }
+}
+
+/// Insert an entry, or replace the one of the same name in the layout the manifest already uses
+/// for it, as a `[dependencies.name]` table or an inline value. An entry that already says the
+/// same is left untouched.
+fn replace(table: &mut dyn TableLike, name: &str, item: &Item) {
+    let Some(existing) = table.get_mut(name) else {
+        table.insert(name, item.clone());
+        return;
+    };
+    if specification(existing) == specification(item) {
+        return;
+    }
+    match (&*existing, item) {
+        (Item::Table(current), Item::Value(Value::InlineTable(inline))) => {
+            let mut replacement = inline.clone().into_table();
+            *replacement.decor_mut() = current.decor().clone();
+            if let Some(position) = current.position() {
+                replacement.set_position(position);
+            }
+            *existing = Item::Table(replacement);
+        }
+        (Item::Value(current), Item::Value(value)) => {
+            let mut replacement = value.clone();
+            *replacement.decor_mut() = current.decor().clone();
+            *existing = Item::Value(replacement);
+        }
+        // A table replaced by a plain version moves to the other inline entries
+        _ => {
+            table.remove(name);
+            table.insert(name, item.clone());
+        }
+    }
+}
+
+/// The content of an entry without its formatting, to tell whether two entries say the same.
+fn specification(item: &Item) -> String {
+    match item.as_table_like() {
+        Some(table) => {
+            let mut entries: Vec<String> = table
+                .iter()
+                .map(|(key, value)| format!("{} = {}", key, specification(value)))
+                .collect();
+            entries.sort();
+            entries.join(", ")
+        }
+        None => item.to_string().trim().to_string(),
+    }
+}
+
+/// A dependency as it is written in a manifest, on one line.
+fn describe(name: &str, item: &Item) -> String {
+    let value = match item {
+        Item::Table(table) => {
+            let mut inline = table.clone().into_inline_table();
+            inline.fmt();
+            inline.to_string()
+        }
+        _ => item.to_string(),
+    };
+    format!("{} = {}", name, value.trim())
+}
+
+pub(crate) fn toml_error(path: &Path, source: &str, err: TomlError) -> CodegenError {
+    let message = err.message().trim_end().replace('\n', ": ");
+    match err.span() {
+        Some(range) => CodegenError::at_offset(path, source, range, message),
+        None => CodegenError::new(message).in_file(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_a_malformed_patch() {
+        let mut manifest = Manifest {
+            path: PathBuf::from("Cargo.toml"),
+            original: String::new(),
+            document: DocumentMut::new(),
+        };
+        let err = manifest.add_patch("sha2 = { git = }").unwrap_err();
+        let err = err.to_string();
+        assert!(err.contains("invalid precompile patch"), "{}", err);
+        assert!(err.contains("Cargo.toml"), "{}", err);
+    }
+}
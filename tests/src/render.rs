@@ -0,0 +1,112 @@
+//! Readable output of codegen.
+//!
+//! The generated guest and host programs are pretty-printed and start with a header naming the
+//! template they come from. Each comes with a [`LineMap`] from its lines back to the template, so
+//! a compile error in the generated code can be traced to the template line that caused it.
+
+use crate::codegen::Template;
+use proc_macro2::Span;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+use syn::{Expr, File, Item, Stmt};
+
+/// The lines of a generated file that come from a template, with the template line each starts
+/// at.
+#[derive(Debug, Clone)]
+pub struct LineMap {
+    pub generated: PathBuf,
+    pub template: PathBuf,
+    lines: Vec<(RangeInclusive<usize>, usize)>,
+}
+
+impl LineMap {
+    /// The template line of a line of the generated file, taken from the innermost item,
+    /// statement or expression around it that comes from the template.
+    pub fn template_line(&self, line: usize) -> Option<usize> {
+        self.lines
+            .iter()
+            .filter(|(lines, _)| lines.contains(&line))
+            .min_by_key(|(lines, _)| lines.end() - lines.start())
+            .map(|(_, template_line)| *template_line)
+    }
+}
+
+/// Pretty-print a file generated from `template` that is written to `path`.
+pub(crate) fn render(file: &File, template: &Template, path: &Path) -> (String, LineMap) {
+    let name = template
+        .path
+        .file_stem()
+        .unwrap_or_default()
+        .to_string_lossy();
+    let code = format!(
+        "// Generated by zkbench codegen {} from the `{}` template, {}.\n\
+         // Edit the template instead, this file is overwritten when the template is generated.\n\n{}",
+        env!("CARGO_PKG_VERSION"),
+        name,
+        template.path.display(),
+        prettyplease::unparse(file)
+    );
+    let line_map = LineMap {
+        generated: path.to_path_buf(),
+        template: template.path.clone(),
+        lines: map_lines(file, &code, template),
+    };
+    (code, line_map)
+}
+
+/// Pair the nodes of the syntax tree with the same nodes parsed back from the printed code. The
+/// spans of the first point into the template where the code was taken from it, the spans of the
+/// second at the lines the code was printed on.
+fn map_lines(file: &File, code: &str, template: &Template) -> Vec<(RangeInclusive<usize>, usize)> {
+    let Ok(printed) = syn::parse_file(code) else {
+        return Vec::new();
+    };
+    let mut generated_spans = Spans::default();
+    generated_spans.visit_file(file);
+    let mut printed_spans = Spans::default();
+    printed_spans.visit_file(&printed);
+    // printing does not change the tree, but nothing can be mapped if it ever does
+    if generated_spans.0.len() != printed_spans.0.len() {
+        return Vec::new();
+    }
+
+    generated_spans
+        .0
+        .into_iter()
+        .zip(printed_spans.0)
+        .filter_map(|(generated, printed)| {
+            let template_line = template_line(generated, template)?;
+            Some((printed.start().line..=printed.end().line, template_line))
+        })
+        .collect()
+}
+
+/// The template line a span starts at, `None` for code codegen added.
+fn template_line(span: Span, template: &Template) -> Option<usize> {
+    // spans of other parsed sources have lines too, only the text tells them apart
+    let text = span.source_text()?;
+    (template.source.get(span.byte_range()) == Some(text.as_str())).then(|| span.start().line)
+}
+
+/// The spans of every item, statement and expression, in the order they appear.
+#[derive(Default)]
+struct Spans(Vec<Span>);
+
+impl<'ast> Visit<'ast> for Spans {
+    fn visit_item(&mut self, item: &'ast Item) {
+        self.0.push(item.span());
+        visit::visit_item(self, item);
+    }
+
+    fn visit_stmt(&mut self, stmt: &'ast Stmt) {
+        self.0.push(stmt.span());
+        visit::visit_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &'ast Expr) {
+        self.0.push(expr.span());
+        visit::visit_expr(self, expr);
+    }
+}
@@ -0,0 +1,619 @@
+//! Runs one benchmark end to end: codegen, guest and host builds, the host run, and result
+//! parsing. The host program measures itself, see the `benchmarker` crate.
+//!
+//! Every phase returns a [`RunError`] instead of leaving the remaining phases to run on a
+//! half-built project. Child processes are wrapped in [`ChildGuard`], which starts each one in a
+//! process group of its own and kills the whole group, the compilers Cargo started included, if
+//! the run is aborted. As the children are out of the terminal's process group, Ctrl-C only
+//! reaches `zkbench`: [`stop_on_interrupt`] kills the running children, and the run fails with
+//! [`RunError::Interrupted`] so everything it holds is dropped before exiting.
+//!
+//! Each run works in its own copy of the zkVM project, `<build dir>/<zkvm>/<test>`, with its own
+//! Cargo target directory and logs. The checked-in projects are only read, so tests can run
+//! concurrently and a failed run leaves its workspace behind for inspection.
+
+use crate::codegen::CodeGenerator;
+use crate::config::Config;
+use crate::diagnostics::CodegenError;
+use crate::env_adapters::{self, CodeEnv, Layout};
+use crate::render::LineMap;
+use benchmarker::record::{locked_version, parse_input_parameters, RunRecord};
+use benchmarker::results::{
+    BenchmarkResult, ExecutionResult, NativeResult, OutputCheck, ParseError, ParseOptions,
+};
+use benchmarker::{Mode, ProofKind, RunConfig};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStderr, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard, Once};
+
+/// Set once Ctrl-C was pressed, no child is started after that.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// The process ids of the children that were not waited for yet, which lead their groups. Held
+/// while a child is started, so Ctrl-C never misses one.
+static CHILDREN: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Vm {
+    Risc0,
+    Sp1,
+    Valida,
+    Native,
+}
+
+impl Vm {
+    pub const ALL: [Vm; 4] = [Vm::Risc0, Vm::Sp1, Vm::Valida, Vm::Native];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Vm::Risc0 => "risc0",
+            Vm::Sp1 => "sp1",
+            Vm::Valida => "valida",
+            Vm::Native => "native",
+        }
+    }
+
+    /// The environment that generates code into the project, or a copy of it, at `root`.
+    pub fn env(&self, root: &Path, layout: &Layout) -> Box<dyn CodeEnv> {
+        let root = root.to_path_buf();
+        let layout = layout.clone();
+        match self {
+            Vm::Risc0 => Box::new(env_adapters::Risc0Env { root, layout }),
+            Vm::Sp1 => Box::new(env_adapters::Sp1Env { root, layout }),
+            Vm::Valida => Box::new(env_adapters::ValidaEnv { root, layout }),
+            Vm::Native => Box::new(env_adapters::NativeEnv { root, layout }),
+        }
+    }
+
+    /// Commands that compile the guest program, run from the given directory of the workspace.
+    fn guest_builds(&self) -> Vec<(&'static str, Vec<&'static str>)> {
+        match self {
+            Vm::Risc0 => vec![("methods", vec!["cargo", "build", "--release"])],
+            Vm::Sp1 => vec![("program", vec!["cargo", "prove", "build"])],
+            Vm::Valida => vec![("program", vec!["cargo", "+valida", "build", "--release"])],
+            // the guest is a library of the native host and is built with it
+            Vm::Native => vec![],
+        }
+    }
+
+    /// Commands that compile the host program, run from the given directory of the workspace.
+    fn host_builds(&self) -> Vec<(&'static str, Vec<&'static str>)> {
+        match self {
+            Vm::Risc0 => vec![("host", vec!["cargo", "build", "--release"])],
+            Vm::Sp1 => vec![(
+                "script",
+                vec!["cargo", "build", "--release", "--bin", "fibonacci"],
+            )],
+            Vm::Valida => vec![("script", vec!["cargo", "build", "--release"])],
+            Vm::Native => vec![(".", vec!["cargo", "build", "--release"])],
+        }
+    }
+
+    /// The directory of the workspace the host runs in, its binary from there and its arguments.
+    fn host_command(&self) -> (&'static str, &'static str, Vec<&'static str>) {
+        match self {
+            Vm::Risc0 => ("host", "../target/release/host", vec![]),
+            Vm::Sp1 => (".", "target/release/fibonacci", vec!["--prove"]),
+            Vm::Valida => ("script", "../target/release/valida-script", vec![]),
+            Vm::Native => (".", "target/release/native-host", vec![]),
+        }
+    }
+
+    /// The lockfile of the workspace that pins the SDK version reported in the results.
+    fn lockfile(&self) -> Option<&'static str> {
+        match self {
+            Vm::Risc0 | Vm::Sp1 => Some("Cargo.lock"),
+            Vm::Valida => Some("program/Cargo.lock"),
+            Vm::Native => None,
+        }
+    }
+
+    /// The proof kinds the host template maps to prover options.
+    fn proof_kinds(&self) -> &'static [ProofKind] {
+        match self {
+            Vm::Risc0 => &[
+                ProofKind::Composite,
+                ProofKind::Succinct,
+                ProofKind::Groth16,
+            ],
+            Vm::Sp1 => &[
+                ProofKind::Composite,
+                ProofKind::Compressed,
+                ProofKind::Groth16,
+                ProofKind::Plonk,
+            ],
+            Vm::Valida | Vm::Native => &[],
+        }
+    }
+
+    /// Whether the host template has an execute-only mode that reports cycles.
+    fn supports_execute(&self) -> bool {
+        matches!(self, Vm::Risc0 | Vm::Sp1)
+    }
+
+    fn sdk_package(&self) -> Option<&'static str> {
+        match self {
+            Vm::Risc0 => Some("risc0-zkvm"),
+            Vm::Sp1 => Some("sp1-sdk"),
+            Vm::Valida => Some("valida-rs"),
+            Vm::Native => None,
+        }
+    }
+}
+
+impl std::str::FromStr for Vm {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "risc0" => Ok(Vm::Risc0),
+            "sp1" => Ok(Vm::Sp1),
+            "valida" => Ok(Vm::Valida),
+            "native" => Ok(Vm::Native),
+            _ => Err(format!(
+                "unknown zkVM {:?}, expected risc0, sp1, valida or native",
+                name
+            )),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RunError {
+    /// The test template could not be turned into guest and host code
+    Codegen(CodegenError),
+    /// A build or the host program exited unsuccessfully
+    CommandFailed {
+        phase: &'static str,
+        command: String,
+        status: ExitStatus,
+    },
+    Io {
+        phase: &'static str,
+        source: io::Error,
+    },
+    Results(ParseError),
+    /// The zkVM has no support for the requested mode
+    Unsupported(String),
+    /// The committed outputs differ from the template's `expected()`, the results are still saved
+    OutputMismatch {
+        results: PathBuf,
+    },
+    /// Ctrl-C was pressed
+    Interrupted,
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunError::Codegen(err) => write!(f, "template codegen failed:\n\n{}", err),
+            RunError::CommandFailed {
+                phase,
+                command,
+                status,
+            } => write!(f, "{} failed: `{}` exited with {}", phase, command, status),
+            RunError::Io { phase, source } => write!(f, "{} failed: {}", phase, source),
+            RunError::Results(err) => write!(f, "parsing results failed: {}", err),
+            RunError::Unsupported(message) => write!(f, "{}", message),
+            RunError::OutputMismatch { results } => write!(
+                f,
+                "committed outputs do not match expected() (results saved to {})",
+                results.display()
+            ),
+            RunError::Interrupted => write!(f, "interrupted"),
+        }
+    }
+}
+
+impl std::error::Error for RunError {}
+
+/// The error of a phase that could not be carried out, [`RunError::Interrupted`] once Ctrl-C was
+/// pressed, as the children were killed or not started then.
+fn io_error(phase: &'static str) -> impl FnOnce(io::Error) -> RunError {
+    move |source| match interrupted() {
+        true => RunError::Interrupted,
+        false => RunError::Io { phase, source },
+    }
+}
+
+/// Kills the process group of the wrapped child unless the child has already been waited for.
+pub struct ChildGuard {
+    child: Option<Child>,
+}
+
+impl ChildGuard {
+    /// Start `command` as the leader of a new process group. Fails with
+    /// [`io::ErrorKind::Interrupted`] once Ctrl-C was pressed.
+    pub fn spawn(command: &mut Command) -> io::Result<ChildGuard> {
+        #[cfg(unix)]
+        command.process_group(0);
+        let mut children = children();
+        if interrupted() {
+            return Err(io::ErrorKind::Interrupted.into());
+        }
+        let child = command.spawn()?;
+        children.push(child.id());
+        Ok(ChildGuard { child: Some(child) })
+    }
+
+    pub fn take_stderr(&mut self) -> Option<ChildStderr> {
+        self.child.as_mut()?.stderr.take()
+    }
+
+    pub fn wait(mut self) -> io::Result<ExitStatus> {
+        let mut child = self.child.take().unwrap();
+        let status = child.wait();
+        children().retain(|id| *id != child.id());
+        status
+    }
+}
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            kill_group(&mut child);
+            let _ = child.wait();
+            children().retain(|id| *id != child.id());
+        }
+    }
+}
+
+#[cfg(unix)]
+fn kill_group(child: &mut Child) {
+    // the child leads its group, so the group id is its process id
+    unsafe { libc::killpg(child.id() as libc::pid_t, libc::SIGKILL) };
+}
+
+#[cfg(not(unix))]
+fn kill_group(child: &mut Child) {
+    let _ = child.kill();
+}
+
+/// On Ctrl-C, kill the process group of every running child instead of exiting right away. The
+/// run then fails with [`RunError::Interrupted`] and cleans up as it unwinds.
+pub fn stop_on_interrupt() {
+    static HANDLER: Once = Once::new();
+    HANDLER.call_once(|| {
+        let handler = ctrlc::set_handler(|| {
+            INTERRUPTED.store(true, Ordering::SeqCst);
+            #[cfg(unix)]
+            for &id in children().iter() {
+                unsafe { libc::killpg(id as libc::pid_t, libc::SIGKILL) };
+            }
+        });
+        if let Err(err) = handler {
+            eprintln!("Failed to install the Ctrl-C handler: {}", err);
+        }
+    });
+}
+
+fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Fail with [`RunError::Interrupted`] once Ctrl-C was pressed.
+fn check_interrupted() -> Result<(), RunError> {
+    match interrupted() {
+        true => Err(RunError::Interrupted),
+        false => Ok(()),
+    }
+}
+
+fn children() -> MutexGuard<'static, Vec<u32>> {
+    // a panic while holding the lock leaves the list itself intact
+    CHILDREN
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+pub struct RunOptions {
+    pub vm: Vm,
+    pub test_name: String,
+    /// Where the projects are
+    pub config: Config,
+    pub templates_dir: PathBuf,
+    pub results_dir: PathBuf,
+    /// Where the workspace of each zkVM and test is created
+    pub build_dir: PathBuf,
+    /// Passed to the host program through `BENCH_WARMUP`, `BENCH_RUNS`,
+    /// `BENCH_MEMORY_SAMPLE_MS` and `BENCH_TDP_WATTS`
+    pub runs: RunConfig,
+    /// Passed to the host program through `BENCH_MODE`
+    pub mode: Mode,
+    /// Passed to the host program through `BENCH_PROOF_KIND`, the zkVM default if not set
+    pub proof_kind: Option<ProofKind>,
+}
+
+/// Run every phase of one benchmark and return the record that was written.
+pub fn run(options: &RunOptions) -> Result<RunRecord, RunError> {
+    let vm = options.vm;
+    if options.mode == Mode::Execute && !vm.supports_execute() {
+        return Err(RunError::Unsupported(format!(
+            "{} has no execute-only mode",
+            vm.name()
+        )));
+    }
+    if let Some(proof_kind) = options.proof_kind {
+        if !vm.proof_kinds().contains(&proof_kind) {
+            return Err(RunError::Unsupported(format!(
+                "{} proofs are not available for {}",
+                proof_kind.name(),
+                vm.name()
+            )));
+        }
+    }
+    let results_dir = &options.results_dir;
+    fs::create_dir_all(results_dir).map_err(io_error("creating the results directory"))?;
+    let workspace = create_workspace(&options.build_dir, vm, &options.test_name)?;
+
+    println!(
+        "==> Generating {} code for {} in {}",
+        vm.name(),
+        options.test_name,
+        workspace.display()
+    );
+    let line_maps = generate(
+        &options.config,
+        vm,
+        &options.templates_dir.join(&options.test_name),
+        &workspace,
+    )?;
+    check_interrupted()?;
+
+    for (dir, command) in vm.guest_builds() {
+        println!("==> Building guest: {}", command.join(" "));
+        run_build("guest build", &workspace, dir, &command, &line_maps)?;
+    }
+    for (dir, command) in vm.host_builds() {
+        println!("==> Building host: {}", command.join(" "));
+        run_build("host build", &workspace, dir, &command, &line_maps)?;
+    }
+
+    // the logs are kept in the workspace, so concurrent runs never share them
+    let bench_log = workspace.join("rust_bench.log");
+    if bench_log.exists() {
+        fs::remove_file(&bench_log).map_err(io_error("removing stale logs"))?;
+    }
+
+    println!("==> Running host");
+    run_host(vm, options, &workspace, &bench_log)?;
+
+    println!("==> Parsing results");
+    let record = collect_results(options, &workspace, &bench_log)?;
+    // execute-only results go to their own file so they never replace a proving run
+    let kind = match options.mode {
+        Mode::Prove => "benchmark",
+        Mode::Execute => "execution",
+    };
+    // each requested proof kind keeps its own results file
+    let test_name = match options.proof_kind {
+        Some(proof_kind) => format!("{}_{}", options.test_name, proof_kind.name()),
+        None => options.test_name.clone(),
+    };
+    let output = results_dir.join(format!("{}_{}_{}_results.json", vm.name(), test_name, kind));
+    record
+        .write_json(&output)
+        .map_err(io_error("writing the results"))?;
+    record
+        .append_csv(&results_dir.join("benchmark_results.csv"))
+        .map_err(io_error("writing the results"))?;
+
+    if record.output_check == Some(OutputCheck::Failed) {
+        return Err(RunError::OutputMismatch { results: output });
+    }
+
+    // the log was only needed to build the record
+    let _ = fs::remove_file(&bench_log);
+    println!("Results saved to {}", output.display());
+    Ok(record)
+}
+
+/// Create the workspace of `test` on `vm` in `build_dir`, `<build dir>/<zkvm>/<test>`, if it does
+/// not exist yet, and return its absolute path.
+pub fn create_workspace(build_dir: &Path, vm: Vm, test_name: &str) -> Result<PathBuf, RunError> {
+    // absolute, as the commands run in different directories of the workspace
+    let workspace = build_dir.join(vm.name()).join(test_name);
+    fs::create_dir_all(&workspace).map_err(io_error("creating the workspace"))?;
+    workspace
+        .canonicalize()
+        .map_err(io_error("creating the workspace"))
+}
+
+/// Copy the project of `vm` to `workspace` and generate the code of the template into the copy.
+/// Returns where the generated lines come from in the template.
+pub fn generate(
+    config: &Config,
+    vm: Vm,
+    template_dir: &Path,
+    workspace: &Path,
+) -> Result<Vec<LineMap>, RunError> {
+    if !template_dir.is_dir() {
+        return Err(RunError::Codegen(
+            CodegenError::new("no test template").in_file(template_dir),
+        ));
+    }
+    let env = vm.env(workspace, config.layout(vm));
+    let guest_output_dir = env.get_guest_output_dir();
+    let host_output_dir = env.get_host_output_dir();
+    let mut generator =
+        CodeGenerator::scaffold(env, config.project(vm)).map_err(RunError::Codegen)?;
+    generator
+        .generate_code(
+            template_dir.to_str().unwrap(),
+            &guest_output_dir,
+            &host_output_dir,
+        )
+        .map_err(RunError::Codegen)?;
+    Ok(generator.line_maps().to_vec())
+}
+
+/// Run a build, and if it fails, point the compiler errors in generated code at the template
+/// lines they come from.
+fn run_build(
+    phase: &'static str,
+    workspace: &Path,
+    dir: &str,
+    command: &[&str],
+    line_maps: &[LineMap],
+) -> Result<(), RunError> {
+    let mut child = ChildGuard::spawn(
+        Command::new(command[0])
+            .args(&command[1..])
+            .current_dir(workspace.join(dir))
+            // the builds go to the target directories of the workspace
+            .env_remove("CARGO_TARGET_DIR")
+            .stderr(Stdio::piped()),
+    )
+    .map_err(io_error(phase))?;
+    // the compiler output is shown as it comes, and kept to find the errors in
+    let mut output = Vec::new();
+    if let Some(stderr) = child.take_stderr() {
+        for line in BufReader::new(stderr).lines() {
+            let line = line.map_err(io_error(phase))?;
+            eprintln!("{}", line);
+            output.push(line);
+        }
+    }
+    let status = child.wait().map_err(io_error(phase))?;
+    check_interrupted()?;
+    if !status.success() {
+        for note in template_locations(&output, line_maps) {
+            eprintln!("{}", note);
+        }
+        return Err(RunError::CommandFailed {
+            phase,
+            command: command.join(" "),
+            status,
+        });
+    }
+    Ok(())
+}
+
+/// A note for every `--> file:line:column` of the compiler output that points into a generated
+/// file at a line taken from the template.
+fn template_locations(output: &[String], line_maps: &[LineMap]) -> Vec<String> {
+    let mut notes = Vec::new();
+    for line in output {
+        let Some(location) = line.trim_start().strip_prefix("--> ") else {
+            continue;
+        };
+        let mut parts = location.rsplitn(3, ':');
+        let (Some(_column), Some(line), Some(file)) = (parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+        let Ok(line) = line.parse() else {
+            continue;
+        };
+        for line_map in line_maps {
+            if !line_map.generated.ends_with(file) {
+                continue;
+            }
+            if let Some(template_line) = line_map.template_line(line) {
+                let note = format!(
+                    "note: {}:{} is generated from {}:{}",
+                    file,
+                    line,
+                    line_map.template.display(),
+                    template_line
+                );
+                if !notes.contains(&note) {
+                    notes.push(note);
+                }
+            }
+        }
+    }
+    notes
+}
+
+fn run_host(
+    vm: Vm,
+    options: &RunOptions,
+    workspace: &Path,
+    bench_log: &Path,
+) -> Result<(), RunError> {
+    let (dir, program, args) = vm.host_command();
+    let dir = workspace.join(dir);
+    let program = dir.join(program);
+    let log = File::create(bench_log).map_err(io_error("host run"))?;
+    let child = ChildGuard::spawn(
+        Command::new(&program)
+            .args(&args)
+            .current_dir(&dir)
+            .env("RUST_LOG", "info")
+            .env("BENCH_WARMUP", options.runs.warmup.to_string())
+            .env("BENCH_RUNS", options.runs.runs.to_string())
+            .env(
+                "BENCH_MEMORY_SAMPLE_MS",
+                options.runs.sample_interval.as_millis().to_string(),
+            )
+            .env("BENCH_TDP_WATTS", options.runs.tdp_watts.to_string())
+            .env("BENCH_MODE", options.mode.name())
+            .envs(
+                options
+                    .proof_kind
+                    .map(|proof_kind| ("BENCH_PROOF_KIND", proof_kind.name())),
+            )
+            .stdout(Stdio::from(log)),
+    )
+    .map_err(io_error("host run"))?;
+    let status = child.wait().map_err(io_error("host run"))?;
+    check_interrupted()?;
+    if !status.success() {
+        return Err(RunError::CommandFailed {
+            phase: "host run",
+            command: format!(
+                "{} (log kept at {})",
+                program.display(),
+                bench_log.display()
+            ),
+            status,
+        });
+    }
+    Ok(())
+}
+
+fn collect_results(
+    options: &RunOptions,
+    workspace: &Path,
+    bench_log: &Path,
+) -> Result<RunRecord, RunError> {
+    let vm = options.vm;
+    let bench_log = fs::read_to_string(bench_log).map_err(io_error("reading the logs"))?;
+
+    let sdk_version = vm
+        .lockfile()
+        .and_then(|lockfile| fs::read_to_string(workspace.join(lockfile)).ok())
+        .and_then(|lockfile| locked_version(&lockfile, vm.sdk_package()?));
+    let record = RunRecord::new(vm.name(), &options.test_name, sdk_version);
+
+    let mut record = if vm == Vm::Native {
+        let result = NativeResult::from_log(&bench_log).map_err(RunError::Results)?;
+        record.with_native(&result)
+    } else if options.mode == Mode::Execute {
+        let result = ExecutionResult::from_log(&bench_log).map_err(RunError::Results)?;
+        record.with_execution(&result)
+    } else {
+        let native_results = options.results_dir.join(format!(
+            "native_{}_benchmark_results.json",
+            options.test_name
+        ));
+        let native_execution_time = RunRecord::read_json(&native_results)
+            .ok()
+            .and_then(|native| native.metric("execution_time"));
+        let parse_options = ParseOptions {
+            require_cycles: vm != Vm::Valida,
+        };
+        let result = BenchmarkResult::from_log(&bench_log, native_execution_time, parse_options)
+            .map_err(RunError::Results)?;
+        record.with_benchmark(&result)
+    };
+    record.inputs = parse_input_parameters(&bench_log);
+    Ok(record)
+}
@@ -22,6 +22,11 @@ fn other_input(n: usize) -> f64 {
     (2 * n) as f64
 }
 
+#[host]
+fn expected() -> u64 {
+    55
+}
+
 #[host]
 fn other_host_function() -> f64 {
     1.0
@@ -3,7 +3,7 @@ use crate::env_adapters::NotImplementedEnv as env;
 fn main() {
     let n: u64 = env::read(); // #input()
     let answer = is_prime(n);
-    let answer_u32 = if answer { 1 } else { 0 };
+    let answer_u32: u32 = if answer { 1 } else { 0 };
     env::commit(&answer_u32);
 }
 
@@ -11,6 +11,13 @@ fn main() {
 fn input() -> u64 {
     230932049823041
 }
+
+#[host]
+fn expected() -> u32 {
+    // 230932049823041 is not prime
+    0
+}
+
 // Implementation from https://en.wikipedia.org/wiki/Primality_test
 fn is_prime(n: u64) -> bool {
     if n <= 1 {
@@ -68,4 +68,11 @@ fn input() -> Vec<u8> {
         data.push(rng.gen());
     }
     data
+}
+
+#[host]
+fn expected() -> Vec<u8> {
+    let mut data = input();
+    data.sort();
+    data
 }
\ No newline at end of file
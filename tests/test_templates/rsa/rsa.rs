@@ -14,9 +14,12 @@ use rsa::{
 
 //implementation from https://github.com/succinctlabs/sp1/blob/dev/examples/rsa/program/src/main.rs
 fn main() {
-    let pk_der: Vec<u8> = env::read(); // #public_key()
-    let message: Vec<u8> = env::read(); // #input_message()
-    let signature: Vec<u8> = env::read(); // #input_signature()
+    #[input(gen = "public_key")]
+    let pk_der: Vec<u8> = env::read();
+    #[input(gen = "input_message")]
+    let message: Vec<u8> = env::read();
+    #[input(gen = "input_signature")]
+    let signature: Vec<u8> = env::read();
 
     let public_key = RsaPublicKey::from_public_key_der(&pk_der).unwrap();
 
@@ -26,7 +29,7 @@ fn main() {
 
     let verification = public_key.verify(Pkcs1v15Sign::new::<Sha256>(), &hashed_msg, &signature);
 
-    let verified = match verification {
+    let verified: bool = match verification {
         Ok(_) => {
             println!("Signature verified successfully.");
             true
@@ -64,6 +67,11 @@ fn input_signature() -> Vec<u8> {
     ]
 }
 
+#[host]
+fn expected() -> bool {
+    true
+}
+
 #[host]
 fn public_key() ->  &'static [u8] {
     include_bytes!("rsa2048-pub.der")
@@ -13,7 +13,7 @@ fn main() {
     hasher.update(data.as_bytes());
 
     let result = hasher.finalize();
-    let hash_hex = hex::encode(result);
+    let hash_hex: String = hex::encode(result);
 
     env::commit(hash_hex);
 }
@@ -22,3 +22,8 @@ fn main() {
 fn input() ->  Vec<u8> {
     b"Hello, world!".to_vec()
 }
+
+#[host]
+fn expected() -> String {
+    String::from("315f5bdb76d078c43b8ac0064e4a0164612b1fce77c869345bfc94c75894edd3")
+}
\ No newline at end of file
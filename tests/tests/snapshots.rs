@@ -0,0 +1,136 @@
+//! Snapshot tests of codegen.
+//!
+//! Every template in `test_templates/` is generated for RISC Zero and SP1 into a copy of the
+//! project under the Cargo temporary directory, and the generated guest and host programs and
+//! the project manifests are compared against `tests/snapshots/<zkvm>/<template>.snap`. A
+//! template that fails to generate is snapshotted with its error. Run with `UPDATE_SNAPSHOTS=1`
+//! to write the snapshots after an intended change.
+
+use similar::TextDiff;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tests::codegen::CodeGenerator;
+use tests::config::Config;
+use tests::env_adapters::CodeEnv;
+use tests::runner::Vm;
+
+#[test]
+fn risc0_snapshots() {
+    check_templates(Vm::Risc0);
+}
+
+#[test]
+fn sp1_snapshots() {
+    check_templates(Vm::Sp1);
+}
+
+fn check_templates(vm: Vm) {
+    let config = Config::find().unwrap();
+    let update = env::var_os("UPDATE_SNAPSHOTS").is_some_and(|value| value == "1");
+    let mut mismatches = Vec::new();
+    for template in templates() {
+        let workspace = Path::new(env!("CARGO_TARGET_TMPDIR"))
+            .join("snapshots")
+            .join(vm.name())
+            .join(&template);
+        let env = vm.env(&workspace, config.layout(vm));
+        let actual = generate(env, config.project(vm), &template);
+        let actual = normalize(&actual, &workspace);
+
+        let snapshot = Path::new("tests/snapshots")
+            .join(vm.name())
+            .join(format!("{}.snap", template));
+        if update {
+            fs::create_dir_all(snapshot.parent().unwrap()).unwrap();
+            fs::write(&snapshot, &actual).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&snapshot).unwrap_or_default();
+        if expected != actual {
+            let path = snapshot.display().to_string();
+            mismatches.push(format!(
+                "{}",
+                TextDiff::from_lines(&expected, &actual)
+                    .unified_diff()
+                    .header(&path, "generated")
+            ));
+        }
+    }
+    assert!(
+        mismatches.is_empty(),
+        "generated code differs from the snapshots, rerun with UPDATE_SNAPSHOTS=1 if the change \
+         is intended:\n{}",
+        mismatches.join("\n")
+    );
+}
+
+/// The template names, sorted.
+fn templates() -> Vec<String> {
+    let mut templates: Vec<String> = fs::read_dir("test_templates")
+        .unwrap()
+        .map(|entry| entry.unwrap())
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect();
+    templates.sort();
+    templates
+}
+
+/// Generate a template into a fresh copy of `project`, and return the generated programs and
+/// the manifests, or the codegen error.
+fn generate(env: Box<dyn CodeEnv>, project: &Path, template: &str) -> String {
+    let files = [
+        (
+            "guest main.rs",
+            Path::new(&env.get_guest_output_dir()).join("main.rs"),
+        ),
+        (
+            "host main.rs",
+            Path::new(&env.get_host_output_dir()).join("main.rs"),
+        ),
+        (
+            "guest Cargo.toml",
+            PathBuf::from(env.get_guest_cargo_toml_path()),
+        ),
+        (
+            "host Cargo.toml",
+            PathBuf::from(env.get_host_cargo_toml_path()),
+        ),
+        (
+            "workspace Cargo.toml",
+            PathBuf::from(env.get_workspace_cargo_toml_path()),
+        ),
+    ];
+    let guest_output_dir = env.get_guest_output_dir();
+    let host_output_dir = env.get_host_output_dir();
+
+    let result = CodeGenerator::scaffold(env, project).and_then(|mut generator| {
+        generator.generate_code(
+            &format!("./test_templates/{}", template),
+            &guest_output_dir,
+            &host_output_dir,
+        )
+    });
+    if let Err(err) = result {
+        return format!("=== error ===\n{}", err);
+    }
+
+    let mut snapshot = String::new();
+    for (name, path) in files {
+        let content = fs::read_to_string(&path).unwrap();
+        snapshot.push_str(&format!("=== {} ===\n{}", name, content));
+        if !content.ends_with('\n') {
+            snapshot.push('\n');
+        }
+    }
+    snapshot
+}
+
+/// Replace the paths that depend on the machine with placeholders.
+fn normalize(snapshot: &str, workspace: &Path) -> String {
+    let repository = Path::new("..").canonicalize().unwrap();
+    snapshot
+        .replace(&workspace.to_string_lossy().into_owned(), "<workspace>")
+        .replace(&repository.to_string_lossy().into_owned(), "<repository>")
+}
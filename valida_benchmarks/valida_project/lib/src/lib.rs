@@ -0,0 +1,30 @@
+//! Typed input and output helpers for Valida guest programs.
+//!
+//! Valida programs only see a raw stdin/stdout stream, so every value is exchanged as one
+//! line of JSON. The host script writes one line per `env::read()` and decodes one line per
+//! `env::commit()` from the program output.
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Read the next input value written by the host.
+pub fn read<T: DeserializeOwned>() -> T {
+    let line = valida_rs::io::read_line::<String>().expect("Failed to read input line");
+    serde_json::from_str(&line).expect("Failed to deserialize input")
+}
+
+/// Read the next input as bytes. The stdin is line based, so the host sends them as a JSON
+/// array like any other value.
+pub fn read_slice() -> Vec<u8> {
+    read()
+}
+
+/// Commit a value to the program output so the host can decode it.
+pub fn commit<T: Serialize + ?Sized>(value: &T) {
+    let line = serde_json::to_string(value).expect("Failed to serialize output");
+    valida_rs::io::println(&line);
+}
+
+/// Commit bytes to the program output, as one JSON array line.
+pub fn commit_slice(bytes: &[u8]) {
+    commit(bytes);
+}
@@ -0,0 +1,7 @@
+#![no_main]
+valida_rs::entrypoint!(main);
+
+fn main() {
+    let n: u32 = valida_io::read();
+    valida_io::commit(&n);
+}
@@ -0,0 +1,2 @@
+// Replaced by the host program that codegen generates from `tests/host_templates/valida.rs`.
+fn main() {}